@@ -0,0 +1,76 @@
+use std::collections::BTreeSet;
+
+use clap::ArgMatches;
+use tree_sitter::{Node, Parser};
+
+use crate::cli::report::{self, Report, Status};
+use crate::cli::{self, Inputs};
+
+/// Returns the names of the captures defined by the patterns of a query,
+/// without the captures that predicates refer to.
+pub fn captures(source: &str) -> BTreeSet<String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
+        .expect("Error loading tree-sitter query grammar");
+    let mut names = BTreeSet::new();
+    if let Some(tree) = parser.parse(source, None) {
+        collect(tree.root_node(), source, &mut names);
+    }
+    names
+}
+
+fn collect(node: Node, source: &str, names: &mut BTreeSet<String>) {
+    match node.kind() {
+        "capture" => {
+            names.insert(source[node.start_byte()..node.end_byte()].to_string());
+        }
+        "parameters" => {}
+        _ => {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                collect(child, source, names);
+            }
+        }
+    }
+}
+
+/// Prints the sorted names of the captures defined by all inputs.
+pub fn run(matches: &ArgMatches) -> i32 {
+    if let Err(e) = cli::require_text_output(matches, "captures") {
+        return cli::fail(&e, 1);
+    }
+    let inputs = match Inputs::from_matches(matches) {
+        Ok(inputs) => inputs,
+        Err(e) => return cli::fail(&e, 1),
+    };
+
+    let mut reports = inputs.process(|input| Report {
+        path: input.name(),
+        status: Status::Unchanged,
+        diagnostics: Vec::new(),
+        stdout: captures(&input.text)
+            .into_iter()
+            .map(|name| name + "\n")
+            .collect(),
+    });
+
+    let names: BTreeSet<String> = reports
+        .iter_mut()
+        .flat_map(|report| {
+            std::mem::take(&mut report.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    for name in &names {
+        println!("{}", name);
+    }
+    report::print(report::OutputFormat::Text, &reports);
+
+    match reports.iter().map(|report| report.status).max() {
+        Some(Status::Failed) => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use tree_sitter_query_formatter::{FormatOptions, FormatOptionsBuilder, Layout};
+
+/// The name of the configuration file looked up next to each input file and in
+/// its parent directories.
+pub const CONFIG_FILE_NAME: &str = ".tsqueryfmt.toml";
+
+/// [`Layout`] as written in a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutSetting {
+    Expanded,
+    Auto,
+}
+
+impl From<LayoutSetting> for Layout {
+    fn from(layout: LayoutSetting) -> Self {
+        match layout {
+            LayoutSetting::Expanded => Layout::Expanded,
+            LayoutSetting::Auto => Layout::Auto,
+        }
+    }
+}
+
+impl From<Layout> for LayoutSetting {
+    fn from(layout: Layout) -> Self {
+        match layout {
+            Layout::Expanded => LayoutSetting::Expanded,
+            Layout::Auto => LayoutSetting::Auto,
+        }
+    }
+}
+
+/// The line ending used in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    /// Returns the line ending of the first line of `text`, or `Lf` if there
+    /// is only one line.
+    pub fn detect(text: &str) -> Self {
+        match text.find(['\n', '\r']) {
+            Some(i) if text[i..].starts_with("\r\n") => LineEnding::Crlf,
+            Some(i) if text[i..].starts_with('\r') => LineEnding::Cr,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// Replaces the `\n` line endings of `text` with this line ending.
+    pub fn apply(self, text: String) -> String {
+        match self {
+            LineEnding::Lf => text,
+            _ => text.replace('\n', self.as_str()),
+        }
+    }
+}
+
+/// Style settings from one source, e.g. a configuration file or the command
+/// line. Unset settings fall back to the next source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indent_width: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_layout: Option<LayoutSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_layout: Option<LayoutSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicate_layout: Option<LayoutSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_blank_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_newline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_of_line: Option<LineEnding>,
+}
+
+impl Settings {
+    /// Returns settings with every value taken from `options`.
+    pub fn from_options(options: &FormatOptions) -> Self {
+        Self {
+            indent_width: Some(options.indent_width),
+            max_width: Some(options.max_width),
+            field_layout: Some(options.field_layout.into()),
+            list_layout: Some(options.list_layout.into()),
+            predicate_layout: Some(options.predicate_layout.into()),
+            max_blank_lines: Some(options.max_blank_lines),
+            trailing_newline: Some(options.trailing_newline),
+            end_of_line: None,
+        }
+    }
+
+    /// Overrides the values of `self` with the values set in `other`.
+    pub fn merge(&mut self, other: &Settings) {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+        merge!(
+            indent_width,
+            max_width,
+            field_layout,
+            list_layout,
+            predicate_layout,
+            max_blank_lines,
+            trailing_newline,
+            end_of_line
+        );
+    }
+
+    /// Applies the values that are set to `builder`. The line ending is not a
+    /// formatting option and has to be applied to the output separately.
+    pub fn apply(&self, mut builder: FormatOptionsBuilder) -> FormatOptionsBuilder {
+        if let Some(indent_width) = self.indent_width {
+            builder = builder.indent_width(indent_width);
+        }
+        if let Some(max_width) = self.max_width {
+            builder = builder.max_width(max_width);
+        }
+        if let Some(layout) = self.field_layout {
+            builder = builder.field_layout(layout.into());
+        }
+        if let Some(layout) = self.list_layout {
+            builder = builder.list_layout(layout.into());
+        }
+        if let Some(layout) = self.predicate_layout {
+            builder = builder.predicate_layout(layout.into());
+        }
+        if let Some(max_blank_lines) = self.max_blank_lines {
+            builder = builder.max_blank_lines(max_blank_lines);
+        }
+        if let Some(trailing_newline) = self.trailing_newline {
+            builder = builder.trailing_newline(trailing_newline);
+        }
+        builder
+    }
+}
+
+/// An error reading a configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    Glob {
+        path: PathBuf,
+        error: globset::Error,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ConfigError::Parse { path, error } => {
+                write!(f, "{}: {}", path.display(), error.to_string().trim_end())
+            }
+            ConfigError::Glob { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+/// Settings that apply to the files matching a set of globs.
+#[derive(Debug)]
+struct Override {
+    files: GlobSet,
+    settings: Settings,
+}
+
+/// A parsed configuration file.
+///
+/// ```toml
+/// max_width = 100
+/// list_layout = "auto"
+///
+/// [[overrides]]
+/// files = ["injections.scm"]
+/// max_width = 120
+/// ```
+///
+/// Globs without a `/` match the file name anywhere below the directory of
+/// the configuration file; other globs match the path relative to it.
+#[derive(Debug)]
+pub struct Config {
+    path: PathBuf,
+    settings: Settings,
+    overrides: Vec<Override>,
+}
+
+impl Config {
+    /// Reads the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let path = std::path::absolute(path).map_err(|error| ConfigError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let text = fs::read_to_string(&path).map_err(|error| ConfigError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        Self::parse(path, &text)
+    }
+
+    fn parse(path: PathBuf, text: &str) -> Result<Self, ConfigError> {
+        let parse_error = |error| ConfigError::Parse {
+            path: path.clone(),
+            error,
+        };
+
+        let mut table: toml::Table = toml::from_str(text).map_err(parse_error)?;
+        let overrides = match table.remove("overrides") {
+            Some(value) => Vec::<toml::Table>::deserialize(value).map_err(parse_error)?,
+            None => Vec::new(),
+        };
+        let settings = Settings::deserialize(table).map_err(parse_error)?;
+
+        let overrides = overrides
+            .into_iter()
+            .map(|mut table| {
+                let files = match table.remove("files") {
+                    Some(value) => Vec::<String>::deserialize(value).map_err(parse_error)?,
+                    None => Vec::new(),
+                };
+                let mut builder = GlobSetBuilder::new();
+                for pattern in files {
+                    let pattern = if pattern.contains('/') {
+                        pattern.trim_start_matches('/').to_owned()
+                    } else {
+                        format!("**/{}", pattern)
+                    };
+                    let glob = Glob::new(&pattern).map_err(|error| ConfigError::Glob {
+                        path: path.clone(),
+                        error,
+                    })?;
+                    builder.add(glob);
+                }
+                let files = builder.build().map_err(|error| ConfigError::Glob {
+                    path: path.clone(),
+                    error,
+                })?;
+                let settings = Settings::deserialize(table).map_err(parse_error)?;
+                Ok(Override { files, settings })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            path,
+            settings,
+            overrides,
+        })
+    }
+
+    /// Finds the configuration file that applies to `path` by looking in the
+    /// directory of `path` and then in each of its parent directories.
+    pub fn discover(path: &Path) -> Result<Option<Self>, ConfigError> {
+        let path = std::path::absolute(path).map_err(|error| ConfigError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(&path)
+        };
+        for dir in start.ancestors() {
+            let candidate = dir.join(CONFIG_FILE_NAME);
+            if candidate.is_file() {
+                return Self::load(&candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the path of the configuration file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the settings for the file at `path`, including the overrides
+    /// whose globs match it.
+    pub fn settings_for(&self, path: &Path) -> Settings {
+        let mut settings = self.settings.clone();
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        for entry in &self.overrides {
+            if entry.files.is_match(relative) {
+                settings.merge(&entry.settings);
+            }
+        }
+        settings
+    }
+}
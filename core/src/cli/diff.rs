@@ -0,0 +1,46 @@
+use similar::TextDiff;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Returns a unified diff between the original and the formatted version of
+/// the file at `path`, or an empty string if they are equal.
+pub fn unified_diff(path: &str, original: &str, formatted: &str, color: bool) -> String {
+    if original == formatted {
+        return String::new();
+    }
+
+    let diff = TextDiff::from_lines(original, formatted)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+    if !color {
+        return diff;
+    }
+
+    let mut output = String::with_capacity(diff.len());
+    for line in diff.split_inclusive('\n') {
+        let style = if line.starts_with("---") || line.starts_with("+++") {
+            BOLD
+        } else if line.starts_with("@@") {
+            CYAN
+        } else if line.starts_with('-') {
+            RED
+        } else if line.starts_with('+') {
+            GREEN
+        } else {
+            output.push_str(line);
+            continue;
+        };
+        let text = line.trim_end_matches('\n');
+        output.push_str(style);
+        output.push_str(text);
+        output.push_str(RESET);
+        output.push_str(&line[text.len()..]);
+    }
+    output
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use ec4rs::property::{EndOfLine, FinalNewline, IndentSize, IndentStyle, MaxLineLen, TabWidth};
+
+use crate::cli::config::{LineEnding, Settings};
+
+/// The `.editorconfig` properties that apply to a file.
+pub struct EditorConfig {
+    pub settings: Settings,
+    /// Whether `indent_style = tab` applies. It is not supported: queries are
+    /// always indented with spaces, using `indent_size` (or `tab_width`)
+    /// spaces per level.
+    pub indent_with_tabs: bool,
+}
+
+/// Reads the `.editorconfig` properties that apply to `path` and maps them to
+/// settings.
+pub fn settings_for(path: &Path) -> Result<EditorConfig, ec4rs::Error> {
+    let mut properties = ec4rs::properties_of(path)?;
+    properties.use_fallbacks();
+
+    let indent_width = match properties.get::<IndentSize>() {
+        Ok(IndentSize::Value(size)) => Some(size),
+        Ok(IndentSize::UseTabWidth) => match properties.get::<TabWidth>() {
+            Ok(TabWidth::Value(width)) => Some(width),
+            Err(_) => None,
+        },
+        Err(_) => None,
+    };
+    let max_width = match properties.get::<MaxLineLen>() {
+        Ok(MaxLineLen::Value(length)) => Some(length),
+        Ok(MaxLineLen::Off) | Err(_) => None,
+    };
+    let trailing_newline = match properties.get::<FinalNewline>() {
+        Ok(FinalNewline::Value(value)) => Some(value),
+        Err(_) => None,
+    };
+    let end_of_line = properties
+        .get::<EndOfLine>()
+        .ok()
+        .map(|ending| match ending {
+            EndOfLine::Lf => LineEnding::Lf,
+            EndOfLine::CrLf => LineEnding::Crlf,
+            EndOfLine::Cr => LineEnding::Cr,
+        });
+
+    let indent_with_tabs = matches!(properties.get::<IndentStyle>(), Ok(IndentStyle::Tabs));
+
+    Ok(EditorConfig {
+        settings: Settings {
+            indent_width,
+            max_width,
+            trailing_newline,
+            end_of_line,
+            ..Settings::default()
+        },
+        indent_with_tabs,
+    })
+}
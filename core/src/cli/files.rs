@@ -0,0 +1,200 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use ignore::gitignore::GitignoreBuilder;
+
+/// The name of the ignore file read in every searched directory, in addition
+/// to `.gitignore`.
+pub const IGNORE_FILE_NAME: &str = ".tsqueryfmtignore";
+
+/// An error expanding the inputs given on the command line.
+#[derive(Debug)]
+pub enum FilesError {
+    NotFound(String),
+    NoMatches(String),
+    Glob(globset::Error),
+    Walk(ignore::Error),
+}
+
+impl fmt::Display for FilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilesError::NotFound(input) => write!(f, "{}: no such file or directory", input),
+            FilesError::NoMatches(input) => write!(f, "{}: no files match this pattern", input),
+            FilesError::Glob(error) => write!(f, "{}", error),
+            FilesError::Walk(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<globset::Error> for FilesError {
+    fn from(error: globset::Error) -> Self {
+        FilesError::Glob(error)
+    }
+}
+
+impl From<ignore::Error> for FilesError {
+    fn from(error: ignore::Error) -> Self {
+        FilesError::Walk(error)
+    }
+}
+
+/// Expands the inputs given on the command line into a sorted list of files.
+///
+/// Files are used as given, unless `.gitignore` or `.tsqueryfmtignore` excludes
+/// them, like stdin with `--stdin-filepath`. Directories are searched
+/// recursively for files with one of `extensions`. Other inputs are glob
+/// patterns, matched against the files below their longest directory prefix
+/// without wildcards. Searches also skip hidden files.
+pub fn expand(inputs: &[String], extensions: &[String]) -> Result<Vec<PathBuf>, FilesError> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_file() {
+            if !is_ignored(path) {
+                files.push(path.to_owned());
+            }
+        } else if path.is_dir() {
+            walk(path, |file| has_extension(file, extensions), &mut files)?;
+        } else if is_glob(input) {
+            let matcher = glob_matcher(input)?;
+            let count = files.len();
+            walk(&glob_base(input), |file| matcher.is_match(file), &mut files)?;
+            if files.len() == count {
+                return Err(FilesError::NoMatches(input.clone()));
+            }
+        } else {
+            return Err(FilesError::NotFound(input.clone()));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Returns the directories to watch for changes to the files that `inputs`
+/// expand to, and whether to watch them recursively. Files are watched through
+/// their directory so that editors that save by replacing the file are
+/// noticed.
+pub fn watch_roots(inputs: &[String]) -> Vec<(PathBuf, bool)> {
+    let mut roots: Vec<(PathBuf, bool)> = inputs
+        .iter()
+        .map(|input| {
+            let path = Path::new(input);
+            if path.is_dir() {
+                (path.to_owned(), true)
+            } else if !path.exists() && is_glob(input) {
+                (glob_base(input), true)
+            } else {
+                match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => (parent.to_owned(), false),
+                    _ => (PathBuf::from("."), false),
+                }
+            }
+        })
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Returns whether the file at `path` is excluded by a `.gitignore` or
+/// `.tsqueryfmtignore` in its directory or one of its parents. The file does
+/// not have to exist.
+pub fn is_ignored(path: &Path) -> bool {
+    let Ok(path) = std::path::absolute(path) else {
+        return false;
+    };
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    // Rules closer to the file take precedence, and `.tsqueryfmtignore` takes
+    // precedence over `.gitignore` in the same directory.
+    for dir in parent.ancestors() {
+        for name in [IGNORE_FILE_NAME, ".gitignore"] {
+            let file = dir.join(name);
+            if !file.is_file() {
+                continue;
+            }
+            let mut builder = GitignoreBuilder::new(dir);
+            if builder.add(&file).is_some() {
+                continue;
+            }
+            let Ok(ignore) = builder.build() else {
+                continue;
+            };
+            let matched = ignore.matched_path_or_any_parents(&path, false);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+    }
+    false
+}
+
+fn walk(
+    root: &Path,
+    include: impl Fn(&Path) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), FilesError> {
+    let walker = WalkBuilder::new(root)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let path = entry.path();
+        let path = path.strip_prefix(".").unwrap_or(path);
+        if include(path) {
+            files.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.iter().any(|e| e == extension))
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '[', '{'])
+}
+
+fn glob_matcher(pattern: &str) -> Result<GlobMatcher, FilesError> {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+/// Returns the directory that contains every file matching `pattern`.
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern)
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+    {
+        if let Component::Normal(name) = component
+            && name.to_str().is_some_and(is_glob)
+        {
+            break;
+        }
+        base.push(component);
+    }
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    base
+}
@@ -0,0 +1,388 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use tree_sitter_query_formatter::{
+    FormatOptions, FormatOptionsBuilder, UnknownNodePolicy, diff_edits, format_with_options,
+};
+
+use crate::cli::config::{Config, LineEnding, Settings};
+use crate::cli::report::{self, Diagnostic, Position, Report, Rule, Status};
+use crate::cli::{self, Input, Inputs, diff, editorconfig, fs, tree};
+
+/// What to do with each formatted input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Print the formatted input.
+    Print,
+    /// Print the edits that format the input as JSON.
+    Edits,
+    /// Print the path of the input if it is not formatted.
+    Check,
+    /// Print a unified diff between the input and the formatted input.
+    Diff,
+    /// Replace the input file with the formatted input.
+    Write,
+}
+
+impl Mode {
+    /// Whether the exit code tells if some inputs are not formatted.
+    pub fn checks(self) -> bool {
+        matches!(self, Mode::Check | Mode::Diff)
+    }
+
+    /// The exit code for errors. Checks reserve exit code 1 for inputs that
+    /// are not formatted.
+    fn error_code(self) -> i32 {
+        if self.checks() { 2 } else { 1 }
+    }
+}
+
+/// The settings shared by all inputs.
+struct FormatRun {
+    mode: Mode,
+    show_tree: bool,
+    /// Whether diffs are colored.
+    color: bool,
+    /// The configuration file given with `--config`.
+    config: Option<Config>,
+    /// The settings given as flags.
+    flags: Settings,
+    /// The options that are not style settings.
+    options: FormatOptionsBuilder,
+}
+
+/// The settings that apply to one input.
+struct ResolvedSettings {
+    settings: Settings,
+    /// The configuration file the settings were read from.
+    config_path: Option<PathBuf>,
+    /// Problems with the settings that do not prevent formatting.
+    warnings: Vec<Diagnostic>,
+}
+
+impl FormatRun {
+    /// Resolves the settings for the input at `path`, or for stdin.
+    fn settings(&self, path: Option<&Path>) -> Result<ResolvedSettings, String> {
+        let discovered;
+        let config = match &self.config {
+            Some(config) => Some(config),
+            None => {
+                discovered =
+                    Config::discover(path.unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+                discovered.as_ref()
+            }
+        };
+
+        // Built-in defaults, then .editorconfig, then the configuration file,
+        // then explicit flags.
+        let mut settings =
+            Settings::from_options(&FormatOptions::builder().trailing_newline(true).build());
+        let mut warnings = Vec::new();
+        if let Some(path) = path {
+            let editorconfig = editorconfig::settings_for(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            settings.merge(&editorconfig.settings);
+            if editorconfig.indent_with_tabs {
+                warnings.push(Diagnostic::warning(
+                    Rule::Config,
+                    "`indent_style = tab` from .editorconfig is not supported, indenting with spaces",
+                ));
+            }
+        }
+        if let Some(config) = config {
+            settings.merge(&config.settings_for(path.unwrap_or(Path::new("."))));
+        }
+        settings.merge(&self.flags);
+        Ok(ResolvedSettings {
+            settings,
+            config_path: config.map(|config| config.path().to_owned()),
+            warnings,
+        })
+    }
+
+    /// Formats one input. Ignored inputs and empty files are kept unchanged.
+    fn process(&self, input: Input) -> Report {
+        let Input {
+            path,
+            text: input,
+            stdin,
+            ignored,
+        } = input;
+        if ignored || (!stdin && input.trim().is_empty()) {
+            return self.finish(path, &input, input.clone(), String::new());
+        }
+
+        let name = path.map(|path| path.display().to_string());
+        let resolved = match self.settings(path) {
+            Ok(resolved) => resolved,
+            Err(e) => return Report::failed(name, vec![Diagnostic::error(Rule::Config, e)]),
+        };
+        let mut report = self.format(path, input, &resolved.settings);
+        report.diagnostics.splice(0..0, resolved.warnings);
+        report
+    }
+
+    /// Formats one input with `settings`.
+    fn format(&self, path: Option<&Path>, input: String, settings: &Settings) -> Report {
+        let name = path.map(|path| path.display().to_string());
+        let options = settings.apply(self.options.clone()).build();
+
+        if input.trim().is_empty() {
+            return Report::failed(name, vec![Diagnostic::error(Rule::Io, "Empty input")]);
+        }
+
+        let mut stdout = String::new();
+        if self.show_tree
+            && let Some(tree_output) = tree::print_tree(&input, self.color)
+        {
+            stdout.push_str("Parse tree:\n");
+            stdout.push_str(&tree_output);
+            stdout.push_str("\n\n");
+        }
+
+        // Keep the line endings of the input unless they are configured.
+        let line_ending = settings
+            .end_of_line
+            .unwrap_or_else(|| LineEnding::detect(&input));
+        let formatted = match format_with_options(&input, &options) {
+            Ok(formatted) => line_ending.apply(formatted),
+            Err(e) => return Report::failed(name, Diagnostic::from_format_error(e)),
+        };
+
+        self.finish(path, &input, formatted, stdout)
+    }
+
+    /// Reports the formatted version of `input` according to the mode.
+    fn finish(
+        &self,
+        path: Option<&Path>,
+        input: &str,
+        formatted: String,
+        mut stdout: String,
+    ) -> Report {
+        let name = path.map(|path| path.display().to_string());
+        let status = if formatted == input {
+            Status::Unchanged
+        } else {
+            Status::Changed
+        };
+        let mut diagnostics = Vec::new();
+        if status == Status::Changed && self.mode.checks() {
+            diagnostics.push(unformatted(input, &formatted));
+        }
+
+        match self.mode {
+            Mode::Print => stdout.push_str(&formatted),
+            Mode::Edits => {
+                let edits: Vec<_> = diff_edits(input, &formatted)
+                    .into_iter()
+                    .map(|edit| {
+                        serde_json::json!({
+                            "start": edit.range.start,
+                            "end": edit.range.end,
+                            "new_text": edit.new_text,
+                        })
+                    })
+                    .collect();
+                writeln!(stdout, "{}", serde_json::Value::Array(edits)).unwrap();
+            }
+            Mode::Check => {
+                if status == Status::Changed {
+                    writeln!(stdout, "{}", name.as_deref().unwrap_or("<stdin>")).unwrap();
+                }
+            }
+            Mode::Diff => {
+                stdout.push_str(&diff::unified_diff(
+                    name.as_deref().unwrap_or("<stdin>"),
+                    input,
+                    &formatted,
+                    self.color,
+                ));
+            }
+            Mode::Write => {
+                let path = path.expect("--write requires input files");
+                if status == Status::Changed
+                    && let Err(e) = fs::write_atomic(path, &formatted)
+                {
+                    return Report::failed(
+                        name,
+                        vec![Diagnostic::error(
+                            Rule::Io,
+                            format!("cannot write file: {}", e),
+                        )],
+                    );
+                }
+            }
+        }
+
+        Report {
+            path: name,
+            status,
+            diagnostics,
+            stdout,
+        }
+    }
+}
+
+/// Returns the diagnostic for an input that is not formatted, pointing at the
+/// first change.
+fn unformatted(input: &str, formatted: &str) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(Rule::Format, "File is not formatted");
+    if let Some(edit) = diff_edits(input, formatted).first() {
+        let position = |offset: usize| {
+            let before = &input[..offset];
+            Position {
+                line: before.matches('\n').count() + 1,
+                column: offset - before.rfind('\n').map_or(0, |i| i + 1) + 1,
+            }
+        };
+        diagnostic.start = Some(position(edit.range.start));
+        diagnostic.end = Some(position(edit.range.end));
+    }
+    diagnostic
+}
+
+/// Returns whether the flag `id` is set. Not every subcommand defines every
+/// flag.
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .try_get_one::<bool>(id)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+fn mode(matches: &ArgMatches, check: bool) -> Mode {
+    if flag(matches, "diff") {
+        Mode::Diff
+    } else if check || flag(matches, "check") {
+        Mode::Check
+    } else if flag(matches, "write") {
+        Mode::Write
+    } else if flag(matches, "edits") {
+        Mode::Edits
+    } else {
+        Mode::Print
+    }
+}
+
+/// Formats the inputs, or checks them if `check` is set or with `--check` and
+/// `--diff`.
+pub fn run(matches: &ArgMatches, check: bool) -> i32 {
+    match Inputs::from_matches(matches) {
+        Ok(inputs) => run_inputs(matches, &inputs, check),
+        Err(e) => cli::fail(&e, mode(matches, check).error_code()),
+    }
+}
+
+/// Like [`run`], for inputs that were already collected.
+pub fn run_inputs(matches: &ArgMatches, inputs: &Inputs, check: bool) -> i32 {
+    let flag = |id: &str| flag(matches, id);
+    let mode = mode(matches, check);
+    let error_code = mode.error_code();
+
+    let config = match matches
+        .get_one::<PathBuf>("config")
+        .map(|path| Config::load(path))
+    {
+        Some(Ok(config)) => Some(config),
+        Some(Err(e)) => return cli::fail(&e.to_string(), error_code),
+        None => None,
+    };
+    if mode == Mode::Write && matches!(inputs, Inputs::Stdin(_)) {
+        return cli::fail("--write requires input files", error_code);
+    }
+    // Only the results of checks and writes can be reported in other formats.
+    let output = if flag("print-config") {
+        Some("settings")
+    } else if flag("tree") {
+        Some("parse trees")
+    } else {
+        match mode {
+            Mode::Print => Some("formatted queries"),
+            Mode::Edits => Some("edits"),
+            Mode::Diff => Some("diffs"),
+            Mode::Check | Mode::Write => None,
+        }
+    };
+    if let Some(output) = output
+        && let Err(e) = cli::require_text_output(matches, output)
+    {
+        return cli::fail(&e, error_code);
+    }
+
+    let run = FormatRun {
+        mode,
+        show_tree: flag("tree"),
+        color: cli::color(matches),
+        config,
+        flags: Settings {
+            max_width: matches.get_one::<usize>("width").copied(),
+            ..Settings::default()
+        },
+        options: FormatOptions::builder()
+            .best_effort(flag("best-effort"))
+            .unknown_nodes(
+                match matches
+                    .get_one::<String>("unknown-nodes")
+                    .map(String::as_str)
+                {
+                    Some("error") => UnknownNodePolicy::Error,
+                    _ => UnknownNodePolicy::Verbatim,
+                },
+            )
+            .verify(flag("verify")),
+    };
+
+    if inputs.len() > 1 && (flag("print-config") || mode == Mode::Edits) {
+        return cli::fail(
+            "--print-config and --edits expect a single input",
+            error_code,
+        );
+    }
+
+    if flag("print-config") {
+        let ResolvedSettings {
+            settings,
+            config_path,
+            warnings,
+        } = match run.settings(inputs.first_path()) {
+            Ok(resolved) => resolved,
+            Err(e) => return cli::fail(&e, error_code),
+        };
+        for warning in warnings {
+            eprintln!("{}", warning.to_text(None));
+        }
+        if let Some(config_path) = config_path {
+            println!("# {}", config_path.display());
+        }
+        print!(
+            "{}",
+            toml::to_string(&settings).expect("settings are valid TOML")
+        );
+        return 0;
+    }
+
+    let reports = inputs.process(|input| run.process(input));
+    report::print(cli::output_format(matches), &reports);
+
+    if mode == Mode::Write {
+        let changed = reports
+            .iter()
+            .filter(|report| report.status == Status::Changed)
+            .count();
+        eprintln!(
+            "{} {} changed",
+            changed,
+            if changed == 1 { "file" } else { "files" }
+        );
+    }
+
+    match reports.iter().map(|report| report.status).max() {
+        Some(Status::Failed) => error_code,
+        Some(Status::Changed) if mode.checks() => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,23 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Replaces the contents of the file at `path` with `contents`.
+///
+/// The contents are written to a temporary file in the same directory, which
+/// then replaces the original file, so that the file is never left partially
+/// written. The permissions of the original file are kept.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let permissions = fs::metadata(path)?.permissions();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents.as_bytes())?;
+    file.as_file().sync_all()?;
+    fs::set_permissions(file.path(), permissions)?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
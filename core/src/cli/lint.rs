@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use clap::ArgMatches;
+use tree_sitter::{Node, Parser};
+
+use crate::cli::report::{self, Diagnostic, Report, Rule, Status};
+use crate::cli::{self, Inputs};
+
+/// Returns the problems found in a query.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
+        .expect("Error loading tree-sitter query grammar");
+    let Some(tree) = parser.parse(source, None) else {
+        return vec![Diagnostic::error(Rule::Syntax, "failed to parse input")];
+    };
+
+    let mut diagnostics = Vec::new();
+    syntax_errors(tree.root_node(), &mut diagnostics);
+
+    // Predicates at the top level belong to the pattern before them.
+    let root_node = tree.root_node();
+    let mut cursor = root_node.walk();
+    let mut pattern = Vec::new();
+    for node in root_node.named_children(&mut cursor) {
+        match node.kind() {
+            "comment" => {}
+            "predicate" => pattern.push(node),
+            _ => {
+                undefined_captures(&pattern, source, &mut diagnostics);
+                pattern = vec![node];
+            }
+        }
+    }
+    undefined_captures(&pattern, source, &mut diagnostics);
+    diagnostics
+}
+
+fn syntax_errors(node: Node, diagnostics: &mut Vec<Diagnostic>) {
+    if node.is_error() {
+        diagnostics.push(Diagnostic::error(Rule::Syntax, "syntax error").at(node.range()));
+        return;
+    }
+    if node.is_missing() {
+        diagnostics.push(
+            Diagnostic::error(Rule::Syntax, format!("missing `{}`", node.kind())).at(node.range()),
+        );
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        syntax_errors(child, diagnostics);
+    }
+}
+
+/// Reports the captures that predicates of a pattern refer to but that the
+/// pattern does not define. `pattern` holds the nodes of one top-level
+/// pattern.
+fn undefined_captures(pattern: &[Node], source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut defined = HashSet::new();
+    let mut referenced = Vec::new();
+    for &node in pattern {
+        collect_captures(node, source, false, &mut defined, &mut referenced);
+    }
+    for capture in referenced {
+        let name = &source[capture.start_byte()..capture.end_byte()];
+        if !defined.contains(name) {
+            diagnostics.push(
+                Diagnostic::error(
+                    Rule::UndefinedCapture,
+                    format!("capture `{}` is not defined in this pattern", name),
+                )
+                .at(capture.range()),
+            );
+        }
+    }
+}
+
+fn collect_captures<'a>(
+    node: Node<'a>,
+    source: &'a str,
+    in_predicate: bool,
+    defined: &mut HashSet<&'a str>,
+    referenced: &mut Vec<Node<'a>>,
+) {
+    if node.kind() == "capture" {
+        if in_predicate {
+            referenced.push(node);
+        } else {
+            defined.insert(&source[node.start_byte()..node.end_byte()]);
+        }
+        return;
+    }
+    let in_predicate = in_predicate || node.kind() == "parameters";
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_captures(child, source, in_predicate, defined, referenced);
+    }
+}
+
+/// Reports the problems found in each input. Exits with 1 if there are any.
+pub fn run(matches: &ArgMatches) -> i32 {
+    match Inputs::from_matches(matches) {
+        Ok(inputs) => run_inputs(matches, &inputs),
+        Err(e) => cli::fail(&e, 1),
+    }
+}
+
+/// Like [`run`], for inputs that were already collected.
+pub fn run_inputs(matches: &ArgMatches, inputs: &Inputs) -> i32 {
+    let reports = inputs.process(|input| {
+        let diagnostics = if input.ignored {
+            Vec::new()
+        } else {
+            lint(&input.text)
+        };
+        Report {
+            path: input.name(),
+            status: if diagnostics.is_empty() {
+                Status::Unchanged
+            } else {
+                Status::Failed
+            },
+            diagnostics,
+            stdout: String::new(),
+        }
+    });
+    report::print(cli::output_format(matches), &reports);
+
+    match reports.iter().map(|report| report.status).max() {
+        Some(Status::Failed) => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,174 @@
+pub mod captures;
+pub mod config;
+pub mod diff;
+pub mod editorconfig;
+pub mod files;
+pub mod format;
+pub mod fs;
+pub mod lint;
+pub mod query;
+pub mod report;
+pub mod tree;
+pub mod watch;
+
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use rayon::prelude::*;
+
+use report::{Diagnostic, OutputFormat, Report, Rule};
+
+/// One input of a command.
+pub struct Input<'a> {
+    /// The path of the input file, or the path given with `--stdin-filepath`.
+    pub path: Option<&'a Path>,
+    pub text: String,
+    /// Whether the input was read from stdin.
+    pub stdin: bool,
+    /// Whether the input was read from stdin and `path` is excluded by an
+    /// ignore file.
+    pub ignored: bool,
+}
+
+impl Input<'_> {
+    /// Returns the path used in reports, or `None` for stdin.
+    pub fn name(&self) -> Option<String> {
+        self.path.map(|path| path.display().to_string())
+    }
+}
+
+/// Where the inputs of a command come from.
+pub enum Inputs {
+    /// Stdin, with the path given with `--stdin-filepath`.
+    Stdin(Option<PathBuf>),
+    Files(Vec<PathBuf>),
+}
+
+impl Inputs {
+    /// Collects the inputs from the `input`, `extensions` and
+    /// `stdin-filepath` arguments.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let inputs: Vec<String> = matches
+            .get_many::<String>("input")
+            .map(|inputs| inputs.cloned().collect())
+            .unwrap_or_default();
+        let stdin_path = matches.get_one::<PathBuf>("stdin-filepath").cloned();
+        if inputs.is_empty() {
+            return Ok(Inputs::Stdin(stdin_path));
+        }
+        if stdin_path.is_some() {
+            return Err("--stdin-filepath cannot be used with input files".to_string());
+        }
+        let extensions: Vec<String> = matches
+            .get_many::<String>("extensions")
+            .map(|extensions| extensions.cloned().collect())
+            .unwrap_or_default();
+        files::expand(&inputs, &extensions)
+            .map(Inputs::Files)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Returns the number of inputs.
+    pub fn len(&self) -> usize {
+        match self {
+            Inputs::Stdin(_) => 1,
+            Inputs::Files(paths) => paths.len(),
+        }
+    }
+
+    /// Returns the path of the first input, if it has one.
+    pub fn first_path(&self) -> Option<&Path> {
+        match self {
+            Inputs::Stdin(path) => path.as_deref(),
+            Inputs::Files(paths) => paths.first().map(PathBuf::as_path),
+        }
+    }
+
+    /// Reads the inputs and processes them in parallel. The reports are
+    /// returned in the order of the inputs.
+    pub fn process(&self, f: impl Fn(Input) -> Report + Sync) -> Vec<Report> {
+        match self {
+            Inputs::Stdin(path) => {
+                let path = path.as_deref();
+                let mut text = String::new();
+                if let Err(e) = io::stdin().read_to_string(&mut text) {
+                    return vec![Report::failed(
+                        path.map(|path| path.display().to_string()),
+                        vec![Diagnostic::error(
+                            Rule::Io,
+                            format!("cannot read stdin: {}", e),
+                        )],
+                    )];
+                }
+                let ignored = path.is_some_and(files::is_ignored);
+                vec![f(Input {
+                    path,
+                    text,
+                    stdin: true,
+                    ignored,
+                })]
+            }
+            Inputs::Files(paths) => paths
+                .par_iter()
+                .map(|path| match std::fs::read_to_string(path) {
+                    Ok(text) => f(Input {
+                        path: Some(path),
+                        text,
+                        stdin: false,
+                        ignored: false,
+                    }),
+                    Err(e) => Report::failed(
+                        Some(path.display().to_string()),
+                        vec![Diagnostic::error(
+                            Rule::Io,
+                            format!("cannot read file: {}", e),
+                        )],
+                    ),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the output format selected with `--output-format`.
+pub fn output_format(matches: &ArgMatches) -> OutputFormat {
+    match matches
+        .get_one::<String>("output-format")
+        .map(String::as_str)
+    {
+        Some("json") => OutputFormat::Json,
+        Some("github") => OutputFormat::Github,
+        Some("checkstyle") => OutputFormat::Checkstyle,
+        Some("sarif") => OutputFormat::Sarif,
+        _ => OutputFormat::Text,
+    }
+}
+
+/// Returns an error unless the output format is `text`, for commands whose
+/// output has no structured form.
+pub fn require_text_output(matches: &ArgMatches, output: &str) -> Result<(), String> {
+    match matches.get_one::<String>("output-format") {
+        Some(format) if format != "text" => Err(format!(
+            "--output-format {} cannot be used when printing {}",
+            format, output
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns whether output is colored, as selected with `--color`.
+pub fn color(matches: &ArgMatches) -> bool {
+    match matches.get_one::<String>("color").map(String::as_str) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    }
+}
+
+/// Prints an error that prevents a command from running and returns
+/// `exit_code`.
+pub fn fail(message: &str, exit_code: i32) -> i32 {
+    eprintln!("Error: {}", message);
+    exit_code
+}
@@ -0,0 +1,87 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use clap::ArgMatches;
+use tree_sitter::{Parser, Query, QueryCursor, StreamingIterator};
+
+use crate::cli::report::{self, Diagnostic, Report, Rule, Status};
+use crate::cli::{self, Inputs};
+
+/// Returns the text of the query given on the command line, which is either
+/// the path of a query file or the query itself.
+fn query_source(query: &str) -> Result<String, String> {
+    let path = Path::new(query);
+    if path.is_file() {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+    } else {
+        Ok(query.to_string())
+    }
+}
+
+/// Runs a query against each input, which is itself a query, and prints the
+/// captured nodes as `path:line:column: @capture text`.
+pub fn run(matches: &ArgMatches) -> i32 {
+    if let Err(e) = cli::require_text_output(matches, "query matches") {
+        return cli::fail(&e, 1);
+    }
+    let language = tree_sitter_tsquery::LANGUAGE.into();
+    let source = match query_source(matches.get_one::<String>("query").unwrap()) {
+        Ok(source) => source,
+        Err(e) => return cli::fail(&e, 1),
+    };
+    let query = match Query::new(&language, &source) {
+        Ok(query) => query,
+        Err(e) => return cli::fail(&format!("invalid query: {}", e), 1),
+    };
+    let inputs = match Inputs::from_matches(matches) {
+        Ok(inputs) => inputs,
+        Err(e) => return cli::fail(&e, 1),
+    };
+
+    let reports = inputs.process(|input| {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&language)
+            .expect("Error loading tree-sitter query grammar");
+        let Some(tree) = parser.parse(&input.text, None) else {
+            return Report::failed(
+                input.name(),
+                vec![Diagnostic::error(Rule::Syntax, "failed to parse input")],
+            );
+        };
+
+        let name = input.name();
+        let display_path = name.as_deref().unwrap_or("<stdin>");
+        let mut stdout = String::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, tree.root_node(), input.text.as_bytes());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let start = node.start_position();
+                writeln!(
+                    stdout,
+                    "{}:{}:{}: @{} {}",
+                    display_path,
+                    start.row + 1,
+                    start.column + 1,
+                    query.capture_names()[capture.index as usize],
+                    &input.text[node.byte_range()]
+                )
+                .unwrap();
+            }
+        }
+        Report {
+            path: name,
+            status: Status::Unchanged,
+            diagnostics: Vec::new(),
+            stdout,
+        }
+    });
+    report::print(report::OutputFormat::Text, &reports);
+
+    match reports.iter().map(|report| report.status).max() {
+        Some(Status::Failed) => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+use tree_sitter_query_formatter::{Divergence, FormatError};
+
+/// How the results of a run are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Formatted inputs on stdout, errors on stderr.
+    Text,
+    /// A JSON array with one object per input.
+    Json,
+    /// GitHub Actions workflow commands, shown as annotations.
+    Github,
+    /// Checkstyle XML.
+    Checkstyle,
+    /// SARIF 2.1.0, for code scanning dashboards.
+    Sarif,
+}
+
+/// What happened to one input, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Unchanged,
+    Changed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// The kind of problem a diagnostic reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// The input could not be read or written.
+    Io,
+    /// The settings for the input could not be resolved.
+    Config,
+    /// The input contains a syntax error.
+    Syntax,
+    /// The input contains a node the formatter has no rule for.
+    UnknownNode,
+    /// Verification of the formatted output failed.
+    Verify,
+    /// The input is not formatted.
+    Format,
+    /// A predicate refers to a capture that its pattern does not define.
+    UndefinedCapture,
+}
+
+impl Rule {
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::Io => "io",
+            Rule::Config => "config",
+            Rule::Syntax => "syntax",
+            Rule::UnknownNode => "unknown-node",
+            Rule::Verify => "verify",
+            Rule::Format => "format",
+            Rule::UndefinedCapture => "undefined-capture",
+        }
+    }
+}
+
+/// A one-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<tree_sitter::Point> for Position {
+    fn from(point: tree_sitter::Point) -> Self {
+        Position {
+            line: point.row + 1,
+            column: point.column + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub rule: Rule,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<Position>,
+}
+
+impl Diagnostic {
+    pub fn error(rule: Rule, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            rule,
+            message: message.into(),
+            start: None,
+            end: None,
+        }
+    }
+
+    pub fn warning(rule: Rule, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(rule, message)
+        }
+    }
+
+    pub fn at(mut self, range: tree_sitter::Range) -> Self {
+        self.start = Some(range.start_point.into());
+        self.end = Some(range.end_point.into());
+        self
+    }
+
+    /// Returns the diagnostics for an error returned by the formatter.
+    pub fn from_format_error(error: FormatError) -> Vec<Self> {
+        match error {
+            FormatError::Syntax { ranges } => ranges
+                .into_iter()
+                .map(|range| Diagnostic::error(Rule::Syntax, "syntax error").at(range))
+                .collect(),
+            FormatError::UnknownNode { kind, range } => {
+                vec![
+                    Diagnostic::error(Rule::UnknownNode, format!("unhandled node `{}`", kind))
+                        .at(range),
+                ]
+            }
+            FormatError::Verify(divergence) => {
+                let diagnostic = Diagnostic::error(Rule::Verify, divergence.to_string());
+                vec![match *divergence {
+                    Divergence::Token {
+                        input: Some(token), ..
+                    } => diagnostic.at(token.range),
+                    _ => diagnostic,
+                }]
+            }
+            e => vec![Diagnostic::error(Rule::Io, e.to_string())],
+        }
+    }
+
+    /// Returns the line printed to stderr in the text output format.
+    pub fn to_text(&self, path: Option<&str>) -> String {
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        match (self.start, path) {
+            (Some(start), Some(path)) => format!(
+                "{}: {} at {}:{}:{}",
+                severity, self.message, path, start.line, start.column
+            ),
+            (Some(start), None) => format!(
+                "{}: {} at {}:{}",
+                severity, self.message, start.line, start.column
+            ),
+            (None, Some(path)) => format!("{}: {}: {}", severity, path, self.message),
+            (None, None) => format!("{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// The result of processing one input. The text output is buffered so that
+/// inputs processed in parallel are reported in order.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The path of the input, or `None` for stdin.
+    pub path: Option<String>,
+    pub status: Status,
+    pub diagnostics: Vec<Diagnostic>,
+    #[serde(skip)]
+    pub stdout: String,
+}
+
+impl Report {
+    pub fn failed(path: Option<String>, diagnostics: Vec<Diagnostic>) -> Self {
+        Report {
+            path,
+            status: Status::Failed,
+            diagnostics,
+            stdout: String::new(),
+        }
+    }
+
+    fn display_path(&self) -> &str {
+        self.path.as_deref().unwrap_or("<stdin>")
+    }
+}
+
+/// Prints the results of a run. In the text output format, the output of each
+/// input goes to stdout and its diagnostics go to stderr.
+pub fn print(format: OutputFormat, reports: &[Report]) {
+    if format != OutputFormat::Text {
+        print!("{}", render(format, reports));
+        return;
+    }
+    for report in reports {
+        print!("{}", report.stdout);
+        for diagnostic in &report.diagnostics {
+            // Unformatted inputs are already listed on stdout.
+            if diagnostic.rule != Rule::Format {
+                eprintln!("{}", diagnostic.to_text(report.path.as_deref()));
+            }
+        }
+    }
+}
+
+/// Formats the results of a run in an output format.
+pub fn render(format: OutputFormat, reports: &[Report]) -> String {
+    match format {
+        OutputFormat::Text => reports
+            .iter()
+            .map(|report| report.stdout.as_str())
+            .collect(),
+        OutputFormat::Json => {
+            let mut output = serde_json::to_string_pretty(reports).expect("reports are valid JSON");
+            output.push('\n');
+            output
+        }
+        OutputFormat::Github => render_github(reports),
+        OutputFormat::Checkstyle => render_checkstyle(reports),
+        OutputFormat::Sarif => render_sarif(reports),
+    }
+}
+
+/// Escapes the message of a GitHub workflow command.
+fn escape_github_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property of a GitHub workflow command.
+fn escape_github_property(text: &str) -> String {
+    escape_github_data(text)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+fn render_github(reports: &[Report]) -> String {
+    let mut output = String::new();
+    for report in reports {
+        for diagnostic in &report.diagnostics {
+            write!(
+                output,
+                "::{} file={}",
+                diagnostic.severity.as_str(),
+                escape_github_property(report.display_path())
+            )
+            .unwrap();
+            if let (Some(start), Some(end)) = (diagnostic.start, diagnostic.end) {
+                write!(
+                    output,
+                    ",line={},col={},endLine={},endColumn={}",
+                    start.line, start.column, end.line, end.column
+                )
+                .unwrap();
+            }
+            writeln!(
+                output,
+                ",title={}::{}",
+                diagnostic.rule.id(),
+                escape_github_data(&diagnostic.message)
+            )
+            .unwrap();
+        }
+    }
+    output
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn render_checkstyle(reports: &[Report]) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str("<checkstyle version=\"4.3\">\n");
+    for report in reports {
+        writeln!(
+            output,
+            "  <file name=\"{}\">",
+            escape_xml(report.display_path())
+        )
+        .unwrap();
+        for diagnostic in &report.diagnostics {
+            let start = diagnostic.start.unwrap_or(Position { line: 1, column: 1 });
+            writeln!(
+                output,
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"tree-sitter-query-formatter.{}\"/>",
+                start.line,
+                start.column,
+                diagnostic.severity.as_str(),
+                escape_xml(&diagnostic.message),
+                diagnostic.rule.id()
+            )
+            .unwrap();
+        }
+        output.push_str("  </file>\n");
+    }
+    output.push_str("</checkstyle>\n");
+    output
+}
+
+fn render_sarif(reports: &[Report]) -> String {
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut results = Vec::new();
+    for report in reports {
+        for diagnostic in &report.diagnostics {
+            if !rules.contains(&diagnostic.rule) {
+                rules.push(diagnostic.rule);
+            }
+            let mut location = serde_json::json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": report.display_path() },
+                },
+            });
+            if let (Some(start), Some(end)) = (diagnostic.start, diagnostic.end) {
+                location["physicalLocation"]["region"] = serde_json::json!({
+                    "startLine": start.line,
+                    "startColumn": start.column,
+                    "endLine": end.line,
+                    "endColumn": end.column,
+                });
+            }
+            results.push(serde_json::json!({
+                "ruleId": diagnostic.rule.id(),
+                "level": diagnostic.severity.as_str(),
+                "message": { "text": diagnostic.message },
+                "locations": [location],
+            }));
+        }
+    }
+
+    let sarif = serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules
+                        .iter()
+                        .map(|rule| serde_json::json!({ "id": rule.id() }))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "results": results,
+        }],
+    });
+    let mut output = serde_json::to_string_pretty(&sarif).expect("SARIF is valid JSON");
+    output.push('\n');
+    output
+}
@@ -0,0 +1,349 @@
+use std::fmt::Write;
+
+use clap::ArgMatches;
+use serde::Serialize;
+use tree_sitter::{Parser, Tree, TreeCursor};
+
+use crate::cli::report::{self, Report, Status};
+use crate::cli::{self, Inputs};
+
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// How `tree` prints a parse tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    /// An indented listing of nodes with their fields and positions, which
+    /// points out syntax errors.
+    Text,
+    /// A JSON object per input, with the children of each node nested in it.
+    Json,
+    /// An S-expression, with the anonymous nodes quoted.
+    Sexp,
+    /// A Graphviz digraph.
+    Dot,
+}
+
+/// A zero-based row and column, as reported by tree-sitter.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Point {
+    row: usize,
+    column: usize,
+}
+
+impl From<tree_sitter::Point> for Point {
+    fn from(point: tree_sitter::Point) -> Self {
+        Point {
+            row: point.row,
+            column: point.column,
+        }
+    }
+}
+
+/// A node of a parse tree, detached from the tree so that it can be
+/// serialized.
+#[derive(Debug, Serialize)]
+struct TreeNode {
+    kind: &'static str,
+    named: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'static str>,
+    start_byte: usize,
+    end_byte: usize,
+    start_point: Point,
+    end_point: Point,
+    error: bool,
+    missing: bool,
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Builds the node the cursor is on. With `named_only`, anonymous
+    /// nodes are left out unless they are missing.
+    fn new(cursor: &mut TreeCursor, named_only: bool) -> Self {
+        let node = cursor.node();
+        let field = cursor.field_name();
+        let mut children = Vec::new();
+        if cursor.goto_first_child() {
+            loop {
+                let child = cursor.node();
+                if !named_only || child.is_named() || child.is_missing() {
+                    children.push(TreeNode::new(cursor, named_only));
+                }
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+            cursor.goto_parent();
+        }
+        TreeNode {
+            kind: node.kind(),
+            named: node.is_named(),
+            field,
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            start_point: node.start_position().into(),
+            end_point: node.end_position().into(),
+            error: node.is_error(),
+            missing: node.is_missing(),
+            children,
+        }
+    }
+
+    fn write_text(&self, output: &mut String, source: &str, depth: usize, color: bool) {
+        let indent = "  ".repeat(depth);
+        output.push_str(&indent);
+        if let Some(field) = self.field {
+            write!(output, "{}: ", field).unwrap();
+        }
+        if self.missing {
+            output.push_str("MISSING ");
+        }
+        if self.named {
+            output.push_str(self.kind);
+        } else {
+            write!(output, "{:?}", self.kind).unwrap();
+        }
+        write!(
+            output,
+            " [{}:{}-{}:{}]",
+            self.start_point.row,
+            self.start_point.column,
+            self.end_point.row,
+            self.end_point.column
+        )
+        .unwrap();
+        if self.named && self.children.is_empty() && !self.error {
+            write!(output, " {:?}", &source[self.start_byte..self.end_byte]).unwrap();
+        }
+
+        let marker = if self.error {
+            Some("syntax error")
+        } else if self.missing {
+            Some("missing")
+        } else {
+            None
+        };
+        match marker {
+            Some(marker) if color => {
+                writeln!(output, " {}{}<- {}{}", BOLD, RED, marker, RESET).unwrap()
+            }
+            Some(marker) => writeln!(output, " <- {}", marker).unwrap(),
+            None => output.push('\n'),
+        }
+        if marker.is_some() {
+            self.write_excerpt(output, source, &indent, color);
+        }
+
+        for child in &self.children {
+            child.write_text(output, source, depth + 1, color);
+        }
+    }
+
+    /// Writes the line the node starts on, with the part of the line covered
+    /// by the node underlined.
+    fn write_excerpt(&self, output: &mut String, source: &str, indent: &str, color: bool) {
+        let line_start = source[..self.start_byte].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[self.start_byte..]
+            .find('\n')
+            .map_or(source.len(), |i| self.start_byte + i);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let offset = source[line_start..self.start_byte].chars().count();
+        let width = source[self.start_byte..self.end_byte.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let underline = "^".repeat(width);
+        writeln!(output, "{}  | {}", indent, line).unwrap();
+        if color {
+            writeln!(
+                output,
+                "{}  | {}{}{}{}",
+                indent,
+                " ".repeat(offset),
+                RED,
+                underline,
+                RESET
+            )
+            .unwrap();
+        } else {
+            writeln!(output, "{}  | {}{}", indent, " ".repeat(offset), underline).unwrap();
+        }
+    }
+
+    fn write_sexp(&self, output: &mut String, depth: usize) {
+        if depth > 0 {
+            output.push('\n');
+        }
+        output.push_str(&"  ".repeat(depth));
+        if let Some(field) = self.field {
+            write!(output, "{}: ", field).unwrap();
+        }
+        output.push('(');
+        if self.missing {
+            output.push_str("MISSING ");
+        }
+        if self.named {
+            output.push_str(self.kind);
+        } else {
+            write!(output, "{:?}", self.kind).unwrap();
+        }
+        write!(
+            output,
+            " {}..{} [{}, {}] - [{}, {}]",
+            self.start_byte,
+            self.end_byte,
+            self.start_point.row,
+            self.start_point.column,
+            self.end_point.row,
+            self.end_point.column
+        )
+        .unwrap();
+        for child in &self.children {
+            child.write_sexp(output, depth + 1);
+        }
+        output.push(')');
+    }
+
+    /// Writes the node and its children as DOT statements. `next_id` is the
+    /// id of the next node to write; returns the id of this node.
+    fn write_dot(&self, output: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let kind = if self.named {
+            self.kind.to_string()
+        } else {
+            format!("{:?}", self.kind)
+        };
+        let label = format!(
+            "{}\n{}..{} [{}, {}] - [{}, {}]",
+            kind,
+            self.start_byte,
+            self.end_byte,
+            self.start_point.row,
+            self.start_point.column,
+            self.end_point.row,
+            self.end_point.column
+        );
+        let mut attributes = format!("label={}", dot_string(&label));
+        if !self.named {
+            attributes.push_str(", style=dashed");
+        }
+        if self.error || self.missing {
+            attributes.push_str(", color=red, fontcolor=red");
+        }
+        writeln!(output, "  n{} [{}];", id, attributes).unwrap();
+
+        for child in &self.children {
+            let child_id = child.write_dot(output, next_id);
+            match child.field {
+                Some(field) => writeln!(
+                    output,
+                    "  n{} -> n{} [label={}];",
+                    id,
+                    child_id,
+                    dot_string(field)
+                ),
+                None => writeln!(output, "  n{} -> n{};", id, child_id),
+            }
+            .unwrap();
+        }
+        id
+    }
+}
+
+/// Quotes a DOT string.
+fn dot_string(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+fn parse(input: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
+        .expect("Error loading tree-sitter query grammar");
+    parser.parse(input, None)
+}
+
+/// Returns the parse tree of `input` in the text format.
+pub fn print_tree(input: &str, color: bool) -> Option<String> {
+    render_tree(input, TreeFormat::Text, false, color)
+}
+
+/// Returns the parse tree of `input` in `format`. With `named_only`, the
+/// anonymous nodes are left out. `color` only applies to the text format.
+pub fn render_tree(
+    input: &str,
+    format: TreeFormat,
+    named_only: bool,
+    color: bool,
+) -> Option<String> {
+    let tree = parse(input)?;
+    let root = TreeNode::new(&mut tree.root_node().walk(), named_only);
+    let mut output = String::new();
+    match format {
+        TreeFormat::Text => root.write_text(&mut output, input, 0, color),
+        TreeFormat::Json => {
+            output = serde_json::to_string_pretty(&root).expect("trees are valid JSON");
+            output.push('\n');
+        }
+        TreeFormat::Sexp => {
+            root.write_sexp(&mut output, 0);
+            output.push('\n');
+        }
+        TreeFormat::Dot => {
+            output.push_str("digraph tree {\n  node [shape=box];\n");
+            root.write_dot(&mut output, &mut 0);
+            output.push_str("}\n");
+        }
+    }
+    Some(output)
+}
+
+/// Prints the parse tree of each input.
+pub fn run(matches: &ArgMatches) -> i32 {
+    if let Err(e) = cli::require_text_output(matches, "parse trees") {
+        return cli::fail(&e, 1);
+    }
+    let format = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => TreeFormat::Json,
+        Some("sexp") => TreeFormat::Sexp,
+        Some("dot") => TreeFormat::Dot,
+        _ => TreeFormat::Text,
+    };
+    let named_only = matches.get_flag("named-only");
+    let color = cli::color(matches);
+    let inputs = match Inputs::from_matches(matches) {
+        Ok(inputs) => inputs,
+        Err(e) => return cli::fail(&e, 1),
+    };
+    // Only the text format has room for the path of each input.
+    let headers = inputs.len() > 1 && format == TreeFormat::Text;
+
+    let reports = inputs.process(|input| {
+        let mut stdout = String::new();
+        if headers && let Some(path) = input.path {
+            stdout.push_str(&format!("{}:\n", path.display()));
+        }
+        stdout.push_str(&render_tree(&input.text, format, named_only, color).unwrap_or_default());
+        Report {
+            path: input.name(),
+            status: Status::Unchanged,
+            diagnostics: Vec::new(),
+            stdout,
+        }
+    });
+    report::print(report::OutputFormat::Text, &reports);
+
+    match reports.iter().map(|report| report.status).max() {
+        Some(Status::Failed) => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use clap::ArgMatches;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::cli::{self, Inputs, files};
+
+/// How long to wait for more changes after one is noticed, since saving a
+/// file often produces several events.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Runs `command` on `paths` and records their modification times.
+fn process(
+    command: impl Fn(&Inputs) -> i32,
+    paths: Vec<PathBuf>,
+    processed: &mut HashMap<PathBuf, Option<SystemTime>>,
+) {
+    let count = paths.len();
+    command(&Inputs::Files(paths.clone()));
+    for path in paths {
+        processed.insert(absolute(&path), modified(&path));
+    }
+    eprintln!(
+        "Processed {} {}, watching for changes",
+        count,
+        if count == 1 { "file" } else { "files" }
+    );
+}
+
+/// Runs `command` on the inputs, and then again on the inputs that change
+/// until the process is killed. The exit code of `command` is ignored, so that
+/// syntax errors do not stop the watch.
+pub fn run(matches: &ArgMatches, command: impl Fn(&Inputs) -> i32) -> i32 {
+    let paths = match Inputs::from_matches(matches) {
+        Ok(Inputs::Files(paths)) => paths,
+        Ok(Inputs::Stdin(_)) => return cli::fail("--watch requires input files", 1),
+        Err(e) => return cli::fail(&e, 1),
+    };
+    let inputs: Vec<String> = matches
+        .get_many::<String>("input")
+        .map(|inputs| inputs.cloned().collect())
+        .unwrap_or_default();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
+        Err(e) => return cli::fail(&format!("cannot watch files: {}", e), 1),
+    };
+    for (root, recursive) in files::watch_roots(&inputs) {
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        if let Err(e) = watcher.watch(&absolute(&root), mode) {
+            return cli::fail(&format!("cannot watch {}: {}", root.display(), e), 1);
+        }
+    }
+
+    // The modification times of the files when they were last processed, so
+    // that files written by the command itself are not processed again.
+    let mut processed = HashMap::new();
+    process(&command, paths, &mut processed);
+
+    let mut changed = HashSet::new();
+    while let Ok(event) = receiver.recv() {
+        let mut event = Some(event);
+        while let Some(result) = event {
+            match result {
+                Ok(event) => {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+                    ) {
+                        changed.extend(event.paths);
+                    }
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            event = receiver.recv_timeout(DEBOUNCE).ok();
+        }
+        if changed.is_empty() {
+            continue;
+        }
+
+        // Expand the inputs again to pick up new files and ignore files.
+        let paths = match Inputs::from_matches(matches) {
+            Ok(Inputs::Files(paths)) => paths,
+            Ok(Inputs::Stdin(_)) => Vec::new(),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                changed.clear();
+                continue;
+            }
+        };
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| {
+                let absolute_path = absolute(path);
+                changed.contains(&absolute_path)
+                    && processed
+                        .get(&absolute_path)
+                        .is_none_or(|time| *time != modified(path))
+            })
+            .collect();
+        changed.clear();
+        if !paths.is_empty() {
+            process(&command, paths, &mut processed);
+        }
+    }
+    cli::fail("stopped watching for changes", 1)
+}
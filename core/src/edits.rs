@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use similar::{Algorithm, DiffTag, capture_diff_slices};
+
+use crate::{FormatError, FormatOptions, Formatter};
+
+/// A replacement of a byte range of the original text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The byte range of the original text to replace.
+    pub range: Range<usize>,
+    /// The text to insert in place of `range`.
+    pub new_text: String,
+}
+
+/// Returns `text` with `edits` applied. The edits must be sorted and must not
+/// overlap.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut offset = 0;
+    for edit in edits {
+        output.push_str(&text[offset..edit.range.start]);
+        output.push_str(&edit.new_text);
+        offset = edit.range.end;
+    }
+    output.push_str(&text[offset..]);
+    output
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if "()[]\"@:;!#.*+?/".contains(c) {
+        CharClass::Punctuation
+    } else {
+        CharClass::Word
+    }
+}
+
+/// Splits `text` into runs of whitespace, runs of word characters and single
+/// punctuation characters.
+fn split_tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous = None;
+    for (i, c) in text.char_indices() {
+        let class = char_class(c);
+        if i > start && (class == CharClass::Punctuation || previous != Some(class)) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        previous = Some(class);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// Computes the edits that turn `old` into `new`, diffing at token granularity
+/// so that unchanged tokens are never replaced.
+///
+/// The edits are sorted and do not overlap.
+pub fn diff_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_tokens = split_tokens(old);
+    let new_tokens = split_tokens(new);
+
+    let mut old_offsets = Vec::with_capacity(old_tokens.len() + 1);
+    let mut offset = 0;
+    old_offsets.push(offset);
+    for token in &old_tokens {
+        offset += token.len();
+        old_offsets.push(offset);
+    }
+
+    let mut edits: Vec<TextEdit> = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_tokens, &new_tokens) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+        let range = old_offsets[old_range.start]..old_offsets[old_range.end];
+        let new_text = new_tokens[new_range].concat();
+        match edits.last_mut() {
+            Some(last) if last.range.end == range.start => {
+                last.range.end = range.end;
+                last.new_text.push_str(&new_text);
+            }
+            _ => edits.push(TextEdit { range, new_text }),
+        }
+    }
+    edits
+}
+
+/// Formats a Tree-sitter query string and returns the smallest set of edits
+/// that turn the input into the formatted output.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as
+/// [`format_with_options`](crate::format_with_options).
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{FormatOptions, TextEdit, format_edits};
+///
+/// let edits = format_edits("( identifier )", &FormatOptions::default()).unwrap();
+/// assert_eq!(
+///     edits,
+///     vec![
+///         TextEdit { range: 1..2, new_text: String::new() },
+///         TextEdit { range: 12..13, new_text: String::new() },
+///     ]
+/// );
+/// ```
+pub fn format_edits(input: &str, options: &FormatOptions) -> Result<Vec<TextEdit>, FormatError> {
+    Formatter::new(options.clone())?.format_edits(input)
+}
+
+impl Formatter {
+    /// Formats a Tree-sitter query string and returns the edits that turn the
+    /// input into the formatted output.
+    ///
+    /// See [`format_edits`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`format_edits`].
+    pub fn format_edits(&mut self, input: &str) -> Result<Vec<TextEdit>, FormatError> {
+        let output = self.format(input)?;
+        Ok(diff_edits(input, &output))
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+use crate::Divergence;
+
+/// An error returned when a query cannot be formatted.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// The Tree-sitter query grammar could not be loaded.
+    GrammarLoad(tree_sitter::LanguageError),
+    /// The parser did not produce a tree.
+    Parse,
+    /// The input contains syntax errors at the given ranges.
+    Syntax { ranges: Vec<tree_sitter::Range> },
+    /// The formatter has no rule for a node and
+    /// [`UnknownNodePolicy::Error`](crate::UnknownNodePolicy::Error) is set.
+    UnknownNode {
+        kind: String,
+        range: tree_sitter::Range,
+    },
+    /// Verification of the formatted output failed.
+    Verify(Box<Divergence>),
+    /// The formatted document could not be rendered.
+    Render(std::io::Error),
+    /// The rendered output is not valid UTF-8.
+    Utf8(std::string::FromUtf8Error),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::GrammarLoad(e) => write!(f, "error loading grammar: {}", e),
+            FormatError::Parse => write!(f, "failed to parse input"),
+            FormatError::Syntax { ranges } => {
+                write!(f, "syntax error")?;
+                if let Some(range) = ranges.first() {
+                    write!(
+                        f,
+                        " at {}:{}",
+                        range.start_point.row + 1,
+                        range.start_point.column + 1
+                    )?;
+                }
+                if ranges.len() > 1 {
+                    write!(f, " (and {} more)", ranges.len() - 1)?;
+                }
+                Ok(())
+            }
+            FormatError::UnknownNode { kind, range } => write!(
+                f,
+                "unhandled node `{}` at {}:{}",
+                kind,
+                range.start_point.row + 1,
+                range.start_point.column + 1
+            ),
+            FormatError::Verify(divergence) => write!(f, "verification failed: {}", divergence),
+            FormatError::Render(e) => write!(f, "failed to render output: {}", e),
+            FormatError::Utf8(e) => write!(f, "output is not valid UTF-8: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::GrammarLoad(e) => Some(e),
+            FormatError::Render(e) => Some(e),
+            FormatError::Utf8(e) => Some(e),
+            FormatError::Parse
+            | FormatError::Syntax { .. }
+            | FormatError::UnknownNode { .. }
+            | FormatError::Verify(_) => None,
+        }
+    }
+}
+
+impl From<tree_sitter::LanguageError> for FormatError {
+    fn from(e: tree_sitter::LanguageError) -> Self {
+        FormatError::GrammarLoad(e)
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        FormatError::Render(e)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for FormatError {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        FormatError::Utf8(e)
+    }
+}
@@ -1,411 +1,666 @@
+mod edits;
+mod error;
+mod offsets;
+mod options;
+mod range;
+mod verify;
+
+pub use edits::{TextEdit, apply_edits, diff_edits, format_edits};
+pub use error::FormatError;
+pub use offsets::{OffsetMap, TokenMapping, format_with_cursor};
+pub use options::{FormatOptions, FormatOptionsBuilder, Layout, UnknownNodePolicy};
+pub use range::{FormattedRange, Span, format_range};
+pub use verify::{Divergence, Token, tokens};
+
 use pretty::RcDoc;
-use tree_sitter::{Node, Parser};
+use tree_sitter::{Node, Parser, Tree};
+
+struct Context<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+}
+
+impl Context<'_> {
+    fn indent(&self) -> isize {
+        self.options.indent_width as isize
+    }
+
+    /// Puts `items` on the following lines, one level deeper than the current line.
+    fn block<'a>(&self, items: Vec<RcDoc<'a, ()>>, layout: Layout) -> RcDoc<'a, ()> {
+        match layout {
+            Layout::Expanded => RcDoc::nest(
+                RcDoc::concat(vec![
+                    RcDoc::hardline(),
+                    RcDoc::intersperse(items, RcDoc::hardline()),
+                ]),
+                self.indent(),
+            ),
+            Layout::Auto => RcDoc::group(RcDoc::nest(
+                RcDoc::concat(vec![
+                    RcDoc::line(),
+                    RcDoc::intersperse(items, RcDoc::line()),
+                ]),
+                self.indent(),
+            )),
+        }
+    }
+}
+
+/// Emits source text unchanged, except for its line endings. A comment node
+/// includes the `\r` of a CRLF line ending, which `str::lines` only strips
+/// when a `\n` follows.
+fn verbatim(text: &str) -> RcDoc<'_, ()> {
+    RcDoc::intersperse(
+        text.lines()
+            .map(|line| RcDoc::text(line.trim_end_matches('\r'))),
+        RcDoc::hardline(),
+    )
+}
+
+/// The children of a container that are put on separate lines when the
+/// container is expanded.
+///
+/// A comment must always be followed by a line break, so comments force the
+/// container to expand and move its closing delimiter to a new line when they
+/// come last. A comment that starts on the line where the previous item or
+/// the container's opening tokens end stays on that line.
+struct Items<'a> {
+    docs: Vec<RcDoc<'a, ()>>,
+    header_comment: Option<RcDoc<'a, ()>>,
+    has_comments: bool,
+    ends_with_comment: bool,
+    last_row: Option<usize>,
+}
+
+impl<'a> Items<'a> {
+    fn new() -> Self {
+        Self {
+            docs: Vec::new(),
+            header_comment: None,
+            has_comments: false,
+            ends_with_comment: false,
+            last_row: None,
+        }
+    }
+
+    /// Records a token of the container that comes before its items.
+    fn header(&mut self, node: Node) {
+        self.last_row = Some(node.end_position().row);
+    }
+
+    fn push(&mut self, node: Node, doc: RcDoc<'a, ()>) {
+        self.docs.push(doc);
+        self.ends_with_comment = false;
+        self.last_row = Some(node.end_position().row);
+    }
+
+    fn push_comment(&mut self, node: Node<'a>, ctx: &Context<'a>) {
+        let doc = comment(node, ctx);
+        if self.last_row == Some(node.start_position().row) {
+            match self.docs.last_mut() {
+                Some(last) => {
+                    let previous = std::mem::replace(last, RcDoc::nil());
+                    *last = RcDoc::concat(vec![previous, RcDoc::space(), doc]);
+                }
+                None => self.header_comment = Some(doc),
+            }
+        } else {
+            self.docs.push(doc);
+        }
+        self.has_comments = true;
+        self.ends_with_comment = true;
+        self.last_row = Some(node.end_position().row);
+    }
 
-fn map_named_node_without_captures<'a>(node: Node<'a>, source: &'a str) -> RcDoc<'a, ()> {
+    /// Takes the comment that follows the opening tokens on the same line.
+    fn header_comment(&mut self) -> RcDoc<'a, ()> {
+        match self.header_comment.take() {
+            Some(doc) => RcDoc::concat(vec![RcDoc::space(), doc]),
+            None => RcDoc::nil(),
+        }
+    }
+
+    fn layout(&self, layout: Layout) -> Layout {
+        if self.has_comments {
+            Layout::Expanded
+        } else {
+            layout
+        }
+    }
+
+    /// Returns the closing delimiter, on a new line if the last item is a comment.
+    fn close(&self, delimiter: &'a str) -> RcDoc<'a, ()> {
+        if self.ends_with_comment {
+            RcDoc::concat(vec![RcDoc::hardline(), RcDoc::text(delimiter)])
+        } else {
+            RcDoc::text(delimiter)
+        }
+    }
+}
+
+/// Lays out the quantifier and captures that follow the closing delimiter of
+/// a container.
+fn map_suffix<'a>(
+    closing: Option<Node<'a>>,
+    children: &[Node<'a>],
+    ctx: &Context<'a>,
+) -> Result<RcDoc<'a, ()>, FormatError> {
     let mut docs = Vec::new();
+    let mut previous = closing;
+    let mut after_comment = false;
+    for &child in children {
+        if child.kind() == "comment" {
+            if previous.is_some_and(|p| p.end_position().row == child.start_position().row) {
+                docs.push(RcDoc::space());
+            } else {
+                docs.push(RcDoc::hardline());
+            }
+            docs.push(comment(child, ctx));
+            after_comment = true;
+        } else if after_comment {
+            docs.push(RcDoc::hardline());
+            docs.push(RcDoc::text(
+                &ctx.source[child.start_byte()..child.end_byte()],
+            ));
+            after_comment = false;
+        } else {
+            docs.push(map(child, ctx)?);
+        }
+        previous = Some(child);
+    }
+    // The lines after a comment continue the container, so they are indented.
+    Ok(RcDoc::nest(RcDoc::concat(docs), ctx.indent()))
+}
 
-    let mut field_docs = Vec::new();
+fn comment<'a>(node: Node<'a>, ctx: &Context<'a>) -> RcDoc<'a, ()> {
+    RcDoc::text(ctx.source[node.start_byte()..node.end_byte()].trim_end())
+}
+
+/// Lays out a named node. When `breakable` is set, children that don't fit on
+/// the current line move to the next one.
+fn map_named_node<'a>(
+    node: Node<'a>,
+    ctx: &Context<'a>,
+    breakable: bool,
+) -> Result<RcDoc<'a, ()>, FormatError> {
+    let mut docs = Vec::new();
+    let mut items = Items::new();
+    let mut suffix = Vec::new();
     let mut has_fields = false;
+    let mut closing = None;
 
     for i in 0..node.child_count() {
         if let Some(child) = node.child(i) {
+            if closing.is_some() {
+                suffix.push(child);
+                continue;
+            }
+            let field_name = node.field_name_for_child(i as u32);
             match child.kind() {
-                "(" => docs.push(RcDoc::text("(")),
-                ")" => {}
-                "field_definition" => {
-                    field_docs.push(map(child, source));
-                    has_fields = true;
+                "(" => {
+                    docs.push(RcDoc::text("("));
+                    items.header(child);
                 }
-                "negated_field" => {
-                    field_docs.push(map(child, source));
-                    has_fields = true;
+                ")" => closing = Some(child),
+                "/" => {
+                    docs.push(map(child, ctx)?);
+                    items.header(child);
+                }
+                _ if matches!(field_name, Some("name" | "supertype")) => {
+                    docs.push(map(child, ctx)?);
+                    items.header(child);
                 }
-                "identifier" => docs.push(map(child, source)),
-                "_" => docs.push(map(child, source)),
-                "named_node" => {
-                    // When a named_node (which has no fields) contains nested named_nodes,
-                    // try to fit on one line, but break if too long
-                    let nested_content = RcDoc::group(RcDoc::concat(vec![RcDoc::nest(
-                        RcDoc::concat(vec![RcDoc::line(), map(child, source)]),
-                        2,
-                    )]));
-                    docs.push(nested_content);
+                "field_definition" | "negated_field" => {
+                    items.push(child, map(child, ctx)?);
+                    has_fields = true;
                 }
-                "capture" => {}
-                _ => docs.push(map(child, source)),
+                "comment" => items.push_comment(child, ctx),
+                _ => items.push(child, map(child, ctx)?),
             }
         }
     }
 
-    if has_fields {
-        let nested_fields = RcDoc::nest(
-            RcDoc::concat(vec![
-                RcDoc::hardline(),
-                RcDoc::intersperse(field_docs, RcDoc::hardline()),
-            ]),
-            2,
-        );
-        docs.push(nested_fields);
-        docs.push(RcDoc::text(")"));
-        RcDoc::concat(docs)
+    let close = items.close(")");
+    docs.push(items.header_comment());
+    if has_fields || items.has_comments {
+        // After a lone comment, the closing delimiter is already on a new line.
+        if !items.docs.is_empty() {
+            let layout = items.layout(ctx.options.field_layout);
+            docs.push(ctx.block(items.docs, layout));
+        }
+    } else if breakable {
+        for item in items.docs {
+            docs.push(RcDoc::group(RcDoc::nest(
+                RcDoc::concat(vec![RcDoc::line(), item]),
+                ctx.indent(),
+            )));
+        }
     } else {
-        docs.push(RcDoc::text(")"));
-        RcDoc::concat(docs)
+        for item in items.docs {
+            docs.push(RcDoc::text(" "));
+            docs.push(item);
+        }
     }
+    docs.push(close);
+    docs.push(map_suffix(closing, &suffix, ctx)?);
+
+    Ok(RcDoc::concat(docs))
 }
 
-fn map<'a>(node: Node<'a>, source: &'a str) -> RcDoc<'a, ()> {
-    match node.kind() {
-        "program" => {
-            let mut docs = Vec::new();
-            for i in 0..node.child_count() {
-                if let Some(child) = node.child(i) {
-                    docs.push(map(child, source));
+/// A comment that controls the formatting of the top-level patterns after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    /// `; format-off`: keep everything up to the next `; format-on` unchanged.
+    Off,
+    /// `; format-on`: end of a `; format-off` region.
+    On,
+    /// `; format-ignore`: keep the next pattern unchanged.
+    Ignore,
+}
+
+fn directive(node: Node, source: &str) -> Option<Directive> {
+    if node.kind() != "comment" {
+        return None;
+    }
+    match source[node.start_byte()..node.end_byte()]
+        .trim_start_matches(';')
+        .trim()
+    {
+        "format-off" => Some(Directive::Off),
+        "format-on" => Some(Directive::On),
+        "format-ignore" => Some(Directive::Ignore),
+        _ => None,
+    }
+}
+
+/// If `children[start]` begins a region that is kept unchanged, returns the
+/// index of the last top-level node of that region.
+fn verbatim_region_end(children: &[Node], start: usize, source: &str) -> Option<usize> {
+    let rest = &children[start + 1..];
+    match directive(children[start], source)? {
+        Directive::Off => Some(
+            rest.iter()
+                .position(|&child| directive(child, source) == Some(Directive::On))
+                .map_or(children.len() - 1, |i| start + 1 + i),
+        ),
+        Directive::Ignore => rest
+            .iter()
+            .position(|child| child.kind() != "comment")
+            .map(|i| start + 1 + i),
+        Directive::On => None,
+    }
+}
+
+/// Lays out consecutive top-level patterns and comments.
+fn map_program<'a>(children: &[Node<'a>], ctx: &Context<'a>) -> Result<RcDoc<'a, ()>, FormatError> {
+    let mut docs = Vec::new();
+    let mut previous: Option<Node> = None;
+    let mut i = 0;
+    while i < children.len() {
+        let child = children[i];
+        let region_end = verbatim_region_end(children, i, ctx.source);
+        let last = children[region_end.unwrap_or(i)];
+
+        if let Some(previous) = previous {
+            if child.kind() == "comment"
+                && child.start_position().row == previous.end_position().row
+            {
+                docs.push(RcDoc::space());
+            } else {
+                let blank_lines = child
+                    .start_position()
+                    .row
+                    .saturating_sub(previous.end_position().row + 1)
+                    .min(ctx.options.max_blank_lines);
+                for _ in 0..=blank_lines {
+                    docs.push(RcDoc::hardline());
                 }
             }
-            RcDoc::intersperse(docs, RcDoc::line())
         }
-        "named_node" => {
-            let mut docs = Vec::new();
 
-            let mut field_docs = Vec::new();
-            let mut predicate_docs = Vec::new();
-            let mut has_fields = false;
-            let mut has_capture_after_paren = false;
+        if region_end.is_some() {
+            docs.push(verbatim(&ctx.source[child.start_byte()..last.end_byte()]));
+        } else {
+            docs.push(map(child, ctx)?);
+        }
 
-            for i in 0..node.child_count() {
-                if let Some(child) = node.child(i) {
-                    if child.kind() == ")" {
-                        if i + 1 < node.child_count() {
-                            has_capture_after_paren = true;
-                        }
-                    } else if child.kind() == "field_definition" || child.kind() == "negated_field"
-                    {
-                        has_fields = true;
-                    }
-                }
-            }
+        previous = Some(last);
+        i = region_end.unwrap_or(i) + 1;
+    }
+    Ok(RcDoc::concat(docs))
+}
+
+fn map<'a>(node: Node<'a>, ctx: &Context<'a>) -> Result<RcDoc<'a, ()>, FormatError> {
+    match node.kind() {
+        "program" => {
+            let mut cursor = node.walk();
+            let children: Vec<_> = node.children(&mut cursor).collect();
+            map_program(&children, ctx)
+        }
+        "named_node" => map_named_node(node, ctx, false),
+        "field_definition" => {
+            let mut docs = Vec::new();
+            let mut items = Items::new();
 
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
                     match child.kind() {
-                        "(" => docs.push(RcDoc::text("(")),
-                        ")" => {
-                            if has_capture_after_paren {
-                                docs.push(RcDoc::text(")"));
-                            } else {
-                            }
-                        }
-                        "field_definition" => {
-                            field_docs.push(map(child, source));
-                        }
-                        "negated_field" => {
-                            field_docs.push(map(child, source));
+                        _ if node.field_name_for_child(i as u32) == Some("name") => {
+                            docs.push(map(child, ctx)?);
+                            items.header(child);
                         }
-                        "identifier" => docs.push(map(child, source)),
-                        "_" => docs.push(map(child, source)),
-                        "named_node" => {
-                            docs.push(RcDoc::text(" "));
-                            docs.push(map(child, source));
+                        ":" => {
+                            docs.push(RcDoc::text(":"));
+                            items.header(child);
                         }
-                        "capture" => docs.push(map(child, source)),
-                        "quantifier" => docs.push(map(child, source)),
-                        "predicate" => {
-                            if has_fields {
-                                predicate_docs.push(map(child, source));
-                            } else {
-                                docs.push(RcDoc::text(" "));
-                                docs.push(map(child, source));
-                            }
-                        }
-                        _ => docs.push(map(child, source)),
+                        "comment" => items.push_comment(child, ctx),
+                        "named_node" => items.push(child, map_named_node(child, ctx, true)?),
+                        _ => items.push(child, map(child, ctx)?),
                     }
                 }
             }
 
-            if has_fields {
-                let nested_fields = RcDoc::nest(
-                    RcDoc::concat(vec![
-                        RcDoc::hardline(),
-                        RcDoc::intersperse(field_docs, RcDoc::hardline()),
-                        if !predicate_docs.is_empty() {
-                            RcDoc::concat(vec![
-                                RcDoc::hardline(),
-                                RcDoc::intersperse(predicate_docs, RcDoc::hardline()),
-                            ])
-                        } else {
-                            RcDoc::nil()
-                        },
-                    ]),
-                    2,
-                );
-                docs.push(nested_fields);
-                docs.push(RcDoc::text(")"));
-                RcDoc::concat(docs)
+            docs.push(items.header_comment());
+            if items.has_comments {
+                docs.push(ctx.block(items.docs, Layout::Expanded));
             } else {
-                for predicate_doc in predicate_docs {
-                    docs.push(RcDoc::text(" "));
-                    docs.push(predicate_doc);
-                }
-                if !has_capture_after_paren {
-                    docs.push(RcDoc::text(")"));
-                }
-                RcDoc::concat(docs)
-            }
-        }
-        "field_definition" => {
-            let mut docs = Vec::new();
-
-            if let Some(name_child) = node.child_by_field_name("name") {
-                docs.push(map(name_child, source));
-                docs.push(RcDoc::text(": "));
-            }
-
-            for i in 0..node.child_count() {
-                if let Some(child) = node.child(i) {
-                    if child.kind() == "named_node" {
-                        docs.push(map_named_node_without_captures(child, source));
-                        for j in 0..child.child_count() {
-                            if let Some(capture_child) = child.child(j) {
-                                if capture_child.kind() == "capture" {
-                                    docs.push(map(capture_child, source));
-                                }
-                            }
-                        }
-                    } else if child.kind() != "identifier" && child.kind() != ":" {
-                        docs.push(map(child, source));
-                    }
-                }
+                docs.push(RcDoc::space());
+                docs.extend(items.docs);
             }
 
-            RcDoc::concat(docs)
+            Ok(RcDoc::concat(docs))
         }
         "identifier" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "capture" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(format!(" {}", text))
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(format!(" {}", text)))
         }
         "anonymous_node" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "missing_node" => {
             let mut docs = Vec::new();
+            let mut items = Items::new();
+            let mut suffix = Vec::new();
+            let mut closing = None;
 
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
+                    if closing.is_some() {
+                        suffix.push(child);
+                        continue;
+                    }
                     match child.kind() {
-                        "(" => docs.push(RcDoc::text("(")),
-                        "MISSING" => {
-                            docs.push(RcDoc::text("MISSING"));
-                            if i + 1 < node.child_count() {
-                                if let Some(next_child) = node.child(i + 1) {
-                                    if next_child.kind() != ")" && next_child.kind() != "capture" {
-                                        docs.push(RcDoc::space());
-                                    }
-                                }
-                            }
+                        "(" | "MISSING" => {
+                            docs.push(RcDoc::text(child.kind()));
+                            items.header(child);
                         }
-                        ")" => docs.push(RcDoc::text(")")),
-                        "capture" => docs.push(map(child, source)),
-                        _ => docs.push(map(child, source)),
+                        ")" => closing = Some(child),
+                        "comment" => items.push_comment(child, ctx),
+                        _ => items.push(child, map(child, ctx)?),
                     }
                 }
             }
 
-            RcDoc::concat(docs)
+            let close = items.close(")");
+            docs.push(items.header_comment());
+            if items.has_comments && !items.docs.is_empty() {
+                docs.push(ctx.block(items.docs, Layout::Expanded));
+            } else {
+                for item in items.docs {
+                    docs.push(RcDoc::space());
+                    docs.push(item);
+                }
+            }
+            docs.push(close);
+            docs.push(map_suffix(closing, &suffix, ctx)?);
+
+            Ok(RcDoc::concat(docs))
         }
         "quantifier" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "grouping" => {
             let mut docs = Vec::new();
-            let mut child_docs = Vec::new();
-            let mut quantifier_docs = Vec::new();
-            let mut capture_docs = Vec::new();
+            let mut items = Items::new();
+            let mut suffix = Vec::new();
+            let mut closing = None;
 
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
+                    if closing.is_some() {
+                        suffix.push(child);
+                        continue;
+                    }
                     match child.kind() {
-                        "(" => docs.push(RcDoc::text("(")),
-                        ")" => {}
-                        "named_node" => {
-                            child_docs.push(map(child, source));
-                        }
-                        "anonymous_node" => {
-                            child_docs.push(map(child, source));
-                        }
-                        "predicate" => {
-                            child_docs.push(map(child, source));
-                        }
-                        "." => {
-                            child_docs.push(RcDoc::text("."));
-                        }
-                        "quantifier" => {
-                            quantifier_docs.push(map(child, source));
+                        "(" => {
+                            docs.push(RcDoc::text("("));
+                            items.header(child);
                         }
-                        "capture" => {
-                            capture_docs.push(map(child, source));
-                        }
-                        _ => docs.push(map(child, source)),
+                        ")" => closing = Some(child),
+                        "comment" => items.push_comment(child, ctx),
+                        _ => items.push(child, map(child, ctx)?),
                     }
                 }
             }
 
-            if child_docs.len() > 1 {
+            docs.push(items.header_comment());
+            if items.has_comments {
+                docs.push(ctx.block(items.docs, Layout::Expanded));
+                docs.push(RcDoc::hardline());
+            } else if items.docs.len() > 1 {
                 let content = RcDoc::group(RcDoc::nest(
                     RcDoc::concat(vec![
                         RcDoc::line_(),
-                        RcDoc::intersperse(child_docs, RcDoc::line()),
+                        RcDoc::intersperse(items.docs, RcDoc::line()),
                         RcDoc::line_(),
                     ]),
-                    2,
+                    ctx.indent(),
                 ));
                 docs.push(content);
-            } else if child_docs.len() == 1 {
-                docs.push(child_docs.into_iter().next().unwrap());
+            } else {
+                docs.extend(items.docs);
             }
 
             docs.push(RcDoc::text(")"));
+            docs.push(map_suffix(closing, &suffix, ctx)?);
 
-            for quantifier_doc in quantifier_docs {
-                docs.push(quantifier_doc);
-            }
-
-            for capture_doc in capture_docs {
-                docs.push(capture_doc);
-            }
-
-            RcDoc::concat(docs)
+            Ok(RcDoc::concat(docs))
         }
         "list" => {
             let mut docs = Vec::new();
-            let mut child_docs = Vec::new();
-            let mut captures = Vec::new();
+            let mut items = Items::new();
+            let mut suffix = Vec::new();
+            let mut closing = None;
 
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
+                    if closing.is_some() {
+                        suffix.push(child);
+                        continue;
+                    }
                     match child.kind() {
-                        "[" => docs.push(RcDoc::text("[")),
-                        "]" => {}
-                        "capture" => captures.push(map(child, source)),
-                        "anonymous_node" => {
-                            child_docs.push(map(child, source));
-                        }
-                        _ => {
-                            child_docs.push(map(child, source));
+                        "[" => {
+                            docs.push(RcDoc::text("["));
+                            items.header(child);
                         }
+                        "]" => closing = Some(child),
+                        "comment" => items.push_comment(child, ctx),
+                        _ => items.push(child, map(child, ctx)?),
                     }
                 }
             }
 
-            if !child_docs.is_empty() {
-                let content = RcDoc::nest(
-                    RcDoc::concat(vec![
-                        RcDoc::hardline(),
-                        RcDoc::intersperse(child_docs, RcDoc::hardline()),
-                        RcDoc::hardline(),
-                    ]),
-                    2,
-                );
+            docs.push(items.header_comment());
+            if items.docs.is_empty() {
+                docs.push(items.close("]"));
+            } else {
+                let content = match items.layout(ctx.options.list_layout) {
+                    Layout::Expanded => RcDoc::nest(
+                        RcDoc::concat(vec![
+                            RcDoc::hardline(),
+                            RcDoc::intersperse(items.docs, RcDoc::hardline()),
+                            RcDoc::hardline(),
+                        ]),
+                        ctx.indent(),
+                    ),
+                    Layout::Auto => RcDoc::group(RcDoc::nest(
+                        RcDoc::concat(vec![
+                            RcDoc::line_(),
+                            RcDoc::intersperse(items.docs, RcDoc::line()),
+                            RcDoc::line_(),
+                        ]),
+                        ctx.indent(),
+                    )),
+                };
                 docs.push(content);
+                docs.push(RcDoc::text("]"));
             }
 
-            docs.push(RcDoc::text("]"));
+            docs.push(map_suffix(closing, &suffix, ctx)?);
 
-            for capture in captures {
-                docs.push(capture);
-            }
-
-            RcDoc::concat(docs)
+            Ok(RcDoc::concat(docs))
         }
         "_" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "predicate" => {
             let mut docs = Vec::new();
+            let mut items = Items::new();
 
+            // Comments before the first or after the last parameter are
+            // children of the predicate rather than of the parameters. After
+            // such a comment, the parameters become items so that they stay
+            // in source order.
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
-                    docs.push(map(child, source));
+                    match child.kind() {
+                        "parameters" if items.has_comments => {
+                            let mut cursor = child.walk();
+                            for parameter in child.children(&mut cursor) {
+                                if parameter.kind() == "comment" {
+                                    items.push_comment(parameter, ctx);
+                                } else {
+                                    let text =
+                                        &ctx.source[parameter.start_byte()..parameter.end_byte()];
+                                    items.push(parameter, RcDoc::text(text));
+                                }
+                            }
+                        }
+                        ")" => {
+                            docs.push(items.header_comment());
+                            let close = items.close(")");
+                            if !items.docs.is_empty() {
+                                docs.push(ctx.block(items.docs, Layout::Expanded));
+                            }
+                            docs.push(close);
+                            break;
+                        }
+                        "comment" => items.push_comment(child, ctx),
+                        _ => {
+                            docs.push(map(child, ctx)?);
+                            items.header(child);
+                        }
+                    }
                 }
             }
 
-            RcDoc::concat(docs)
+            Ok(RcDoc::concat(docs))
         }
         "predicate_type" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "parameters" => {
             let mut docs = Vec::new();
-            let mut string_docs = Vec::new();
+            let mut items = Items::new();
+            let mut string_count = 0;
 
+            // Parameters before the first string or comment stay on the line of
+            // the predicate name, the rest may be put on separate lines.
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
+                    let text = &ctx.source[child.start_byte()..child.end_byte()];
                     match child.kind() {
-                        "identifier" => {
-                            docs.push(RcDoc::space());
-                            docs.push(map(child, source))
-                        }
-                        "capture" => docs.push(map(child, source)),
+                        "comment" => items.push_comment(child, ctx),
                         "string" => {
-                            string_docs.push(map(child, source));
+                            items.push(child, RcDoc::text(text));
+                            string_count += 1;
                         }
-                        _ => docs.push(map(child, source)),
+                        _ if items.docs.is_empty() && !items.has_comments => {
+                            docs.push(RcDoc::space());
+                            docs.push(RcDoc::text(text));
+                            items.header(child);
+                        }
+                        _ => items.push(child, RcDoc::text(text)),
                     }
                 }
             }
 
-            if string_docs.len() > 1 {
-                let formatted_strings = RcDoc::nest(
-                    RcDoc::concat(vec![
-                        RcDoc::hardline(),
-                        RcDoc::intersperse(string_docs, RcDoc::hardline()),
-                    ]),
-                    2,
-                );
-                docs.push(formatted_strings);
-            } else if string_docs.len() == 1 {
-                docs.push(RcDoc::space());
-                docs.push(string_docs.into_iter().next().unwrap());
+            docs.push(items.header_comment());
+            if items.has_comments || string_count > 1 {
+                let layout = items.layout(ctx.options.predicate_layout);
+                docs.push(ctx.block(items.docs, layout));
+            } else {
+                for item in items.docs {
+                    docs.push(RcDoc::space());
+                    docs.push(item);
+                }
             }
 
-            RcDoc::concat(docs)
+            Ok(RcDoc::concat(docs))
         }
         "string" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "string_content" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "negated_field" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
         "#" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
-        }
-        "(" => RcDoc::text("("),
-        ")" => RcDoc::text(")"),
-        "." => RcDoc::text(" ."),
-        "/" => RcDoc::text("/"),
-        "comment" => {
-            let text = &source[node.start_byte()..node.end_byte()];
-            RcDoc::text(text)
-        }
-        _ => {
-            println!("Did not handle {}", node.kind());
-            RcDoc::nil()
+            let text = &ctx.source[node.start_byte()..node.end_byte()];
+            Ok(RcDoc::text(text))
         }
+        "(" => Ok(RcDoc::text("(")),
+        ")" => Ok(RcDoc::text(")")),
+        "." => Ok(RcDoc::text(".")),
+        "/" => Ok(RcDoc::text("/")),
+        "comment" => Ok(comment(node, ctx)),
+        _ => match ctx.options.unknown_nodes {
+            UnknownNodePolicy::Verbatim => {
+                Ok(verbatim(&ctx.source[node.start_byte()..node.end_byte()]))
+            }
+            UnknownNodePolicy::Error => Err(FormatError::UnknownNode {
+                kind: node.kind().to_string(),
+                range: node.range(),
+            }),
+        },
+    }
+}
+
+/// Collects the ranges of all `ERROR` and `MISSING` nodes below `node`.
+fn collect_syntax_errors(node: Node, ranges: &mut Vec<tree_sitter::Range>) {
+    if node.is_error() || node.is_missing() {
+        ranges.push(node.range());
+        return;
+    }
+    if !node.has_error() {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_syntax_errors(child, ranges);
     }
 }
 
@@ -414,6 +669,9 @@ fn map<'a>(node: Node<'a>, source: &'a str) -> RcDoc<'a, ()> {
 /// Takes a Tree-sitter query as input and formats it according to the grammar rules,
 /// applying consistent indentation and line breaking to improve readability.
 ///
+/// This is a shorthand for [`format_with_options`] using the default options
+/// with the given `width`.
+///
 /// # Arguments
 ///
 /// * `input` - The Tree-sitter query string to format
@@ -426,9 +684,10 @@ fn map<'a>(node: Node<'a>, source: &'a str) -> RcDoc<'a, ()> {
 ///
 /// # Errors
 ///
-/// This function will return an error if:
+/// This function will return a [`FormatError`] if:
 /// - The Tree-sitter grammar cannot be loaded
 /// - The input query cannot be parsed
+/// - The input query contains syntax errors
 /// - The formatted output cannot be rendered
 ///
 /// # Example
@@ -439,20 +698,136 @@ fn map<'a>(node: Node<'a>, source: &'a str) -> RcDoc<'a, ()> {
 /// let query = "(function_definition name: (identifier) @func)";
 /// let formatted = format(query, 80).unwrap();
 /// ```
-pub fn format(input: &str, width: usize) -> Result<String, Box<dyn std::error::Error>> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
-        .map_err(|e| format!("Error loading grammar: {:?}", e))?;
+pub fn format(input: &str, width: usize) -> Result<String, FormatError> {
+    format_with_options(input, &FormatOptions::builder().max_width(width).build())
+}
 
-    let tree = parser.parse(input, None).ok_or("Failed to parse input")?;
+/// Formats a Tree-sitter query string using the given style options.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`format()`]. Syntax errors are
+/// not reported if [`FormatOptions::best_effort`] is set. If
+/// [`FormatOptions::verify`] is set, returns [`FormatError::Verify`] when the
+/// output does not have the same tokens as the input or is not stable under
+/// formatting.
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{format_with_options, FormatOptions};
+///
+/// let options = FormatOptions::builder().indent_width(4).build();
+/// let query = "(function_definition name: (identifier) @func)";
+/// let formatted = format_with_options(query, &options).unwrap();
+/// assert_eq!(formatted, "(function_definition\n    name: (identifier) @func)");
+/// ```
+pub fn format_with_options(input: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    Formatter::new(options.clone())?.format(input)
+}
 
-    let root_node = tree.root_node();
-    let doc = map(root_node, input);
+/// A formatter that keeps its parser between calls.
+///
+/// Creating a parser and loading the query grammar is the most expensive part
+/// of formatting a short query, so callers that format many queries should
+/// reuse a single `Formatter`.
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{FormatOptions, Formatter};
+///
+/// let mut formatter = Formatter::new(FormatOptions::default()).unwrap();
+/// for query in ["(identifier) @variable", "(string) @string"] {
+///     let formatted = formatter.format(query).unwrap();
+///     assert_eq!(formatted, query);
+/// }
+/// ```
+pub struct Formatter {
+    parser: Parser,
+    options: FormatOptions,
+}
+
+impl Formatter {
+    /// Creates a formatter with the given options.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::GrammarLoad`] if the query grammar cannot be loaded.
+    pub fn new(options: FormatOptions) -> Result<Self, FormatError> {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_tsquery::LANGUAGE.into())?;
+        Ok(Self { parser, options })
+    }
+
+    /// Returns the options used by this formatter.
+    pub fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    /// Parses `input` with the query grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::Parse`] if the parser does not produce a tree.
+    pub fn parse(&mut self, input: &str) -> Result<Tree, FormatError> {
+        self.parser.parse(input, None).ok_or(FormatError::Parse)
+    }
+
+    /// Formats a Tree-sitter query string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`format_with_options`].
+    pub fn format(&mut self, input: &str) -> Result<String, FormatError> {
+        let tree = self.parse(input)?;
+        self.format_tree(&tree, input)
+    }
 
+    /// Formats a query that has already been parsed with the query grammar.
+    ///
+    /// `tree` must be the result of parsing `input`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`format_with_options`].
+    pub fn format_tree(&mut self, tree: &Tree, input: &str) -> Result<String, FormatError> {
+        let output = format_tree(tree, input, &self.options)?;
+
+        if self.options.verify {
+            verify::verify(&mut self.parser, tree, input, &output, &self.options)?;
+        }
+
+        Ok(output)
+    }
+}
+
+fn render(doc: RcDoc<'_, ()>, options: &FormatOptions) -> Result<String, FormatError> {
     let mut w = Vec::new();
-    doc.render(width, &mut w)?;
-    let output = String::from_utf8(w)?;
+    doc.render(options.max_width, &mut w)?;
+    Ok(String::from_utf8(w)?)
+}
+
+fn format_tree(tree: &Tree, input: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    let root_node = tree.root_node();
+    if !options.best_effort {
+        let mut ranges = Vec::new();
+        collect_syntax_errors(root_node, &mut ranges);
+        if !ranges.is_empty() {
+            return Err(FormatError::Syntax { ranges });
+        }
+    }
+
+    let ctx = Context {
+        source: input,
+        options,
+    };
+    let doc = map(root_node, &ctx)?;
+    let mut output = render(doc, options)?;
+
+    if options.trailing_newline && !output.ends_with('\n') {
+        output.push('\n');
+    }
 
     Ok(output)
 }
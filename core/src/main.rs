@@ -1,102 +1,270 @@
-use clap::{Arg, Command};
-use std::fs;
-use std::io::{self, Read};
-use tree_sitter::{Node, Parser};
-use tree_sitter_query_formatter::format;
+mod cli;
 
-fn print_tree_recursive(node: Node, source: &str, depth: usize) -> String {
-    let indent = "  ".repeat(depth);
-    let node_text = if node.child_count() == 0 {
-        let text = &source[node.start_byte()..node.end_byte()];
-        format!(" \"{}\"", text.replace('\n', "\\n"))
-    } else {
-        String::new()
-    };
+use std::ffi::OsString;
+use std::path::PathBuf;
 
-    let mut result = format!("{}{}{}\n", indent, node.kind(), node_text);
+use clap::{Arg, ArgAction, Command};
+use cli::{captures, format, lint, query, tree, watch};
 
-    for i in 0..node.child_count() {
-        if let Some(child) = node.child(i) {
-            result.push_str(&print_tree_recursive(child, source, depth + 1));
-        }
-    }
+fn input_arg() -> Arg {
+    Arg::new("input")
+        .help("Input files, directories or glob patterns; reads stdin if there are none")
+        .num_args(0..)
+}
 
-    result
+fn watch_arg() -> Arg {
+    Arg::new("watch")
+        .long("watch")
+        .help("Run again on the input files that change, until interrupted")
+        .action(ArgAction::SetTrue)
+        .requires("input")
+        .conflicts_with("print-config")
 }
 
-fn print_tree(input: &str) -> Option<String> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
-        .expect("Error loading tree-sitter query grammar");
+fn print_config_arg() -> Arg {
+    Arg::new("print-config")
+        .long("print-config")
+        .help("Print the settings that apply to the input and exit")
+        .action(ArgAction::SetTrue)
+}
 
-    if let Some(tree) = parser.parse(input, None) {
-        let root_node = tree.root_node();
-        return Some(print_tree_recursive(root_node, input, 0));
-    }
+/// The arguments of `fmt`, which is also run when no subcommand is given.
+fn fmt_args() -> [Arg; 8] {
+    [
+        input_arg(),
+        Arg::new("tree")
+            .long("tree")
+            .help("Print the parse tree")
+            .action(ArgAction::SetTrue),
+        Arg::new("edits")
+            .long("edits")
+            .help("Print the edits that format the input as JSON")
+            .action(ArgAction::SetTrue),
+        Arg::new("check")
+            .long("check")
+            .help("Print the inputs that are not formatted instead of formatting them; exits with 1 if there are any and 2 on errors")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("edits"),
+        Arg::new("write")
+            .long("write")
+            .help("Format the input files in place")
+            .action(ArgAction::SetTrue)
+            .requires("input")
+            .conflicts_with_all(["check", "edits"]),
+        Arg::new("diff")
+            .long("diff")
+            .help("Print a unified diff of the changes instead of the formatted inputs; exits like --check")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["check", "edits", "write"]),
+        print_config_arg(),
+        watch_arg().conflicts_with("edits"),
+    ]
+}
 
-    None
+/// Inserts `fmt` into `args` unless they name a subcommand, so that a bare
+/// invocation formats. Global options may come before the subcommand.
+fn insert_default_subcommand(command: &Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    let takes_value = |arg: &Arg| arg.get_action().takes_values();
+    let mut i = 1;
+    while let Some(arg) = args.get(i).and_then(|arg| arg.to_str()) {
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or(long);
+            if matches!(name, "help" | "version") {
+                return args;
+            }
+            // Options of `fmt` are not defined on the root command.
+            match command.get_arguments().find(|a| a.get_long() == Some(name)) {
+                Some(option) if takes_value(option) && !long.contains('=') => i += 1,
+                Some(_) => {}
+                None => break,
+            }
+        } else if let Some(short) = arg.strip_prefix('-')
+            && let Some(name) = short.chars().next()
+        {
+            if matches!(name, 'h' | 'V') {
+                return args;
+            }
+            match command
+                .get_arguments()
+                .find(|a| a.get_short() == Some(name))
+            {
+                Some(option) if takes_value(option) && short.len() == 1 => i += 1,
+                Some(_) => {}
+                None => break,
+            }
+        } else {
+            if arg == "help" || command.find_subcommand(arg).is_some() {
+                return args;
+            }
+            break;
+        }
+        i += 1;
+    }
+    args.insert(1, OsString::from("fmt"));
+    args
 }
 
 fn main() {
-    let matches = Command::new("tree-sitter-query-formatter")
+    let command = Command::new("tree-sitter-query-formatter")
         .version("0.1.0")
         .about("Format tree-sitter queries")
+        .override_usage(
+            "tree-sitter-query-formatter [OPTIONS] [input]...\n       tree-sitter-query-formatter [OPTIONS] <COMMAND>",
+        )
+        .subcommand_required(true)
+        .arg(
+            Arg::new("best-effort")
+                .long("best-effort")
+                .help("Format the input even if it contains syntax errors")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("unknown-nodes")
+                .long("unknown-nodes")
+                .help("What to do with nodes the formatter has no rule for")
+                .value_parser(["verbatim", "error"])
+                .default_value("verbatim")
+                .global(true),
+        )
         .arg(
-            Arg::new("input")
-                .help("Input file or query string")
-                .required(false)
-                .index(1),
+            Arg::new("verify")
+                .long("verify")
+                .help("Check that formatting preserves the query and is idempotent")
+                .action(ArgAction::SetTrue)
+                .global(true),
         )
         .arg(
-            Arg::new("tree")
-                .long("tree")
-                .help("Print the parse tree")
-                .action(clap::ArgAction::SetTrue),
+            Arg::new("color")
+                .long("color")
+                .help("When to color diffs and parse trees")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .global(true),
         )
         .arg(
             Arg::new("width")
                 .long("width")
                 .short('w')
-                .help("Output width")
+                .help("Output width [default: 80]")
                 .value_parser(clap::value_parser!(usize))
-                .default_value("80"),
+                .global(true),
         )
-        .get_matches();
-
-    let width = *matches.get_one::<usize>("width").unwrap();
-    let show_tree = matches.get_flag("tree");
-
-    let input = if let Some(input_arg) = matches.get_one::<String>("input") {
-        fs::read_to_string(input_arg).unwrap_or_else(|e| {
-            eprintln!("Error reading file {}: {}", input_arg, e);
-            std::process::exit(1);
-        })
-    } else {
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer).unwrap_or_else(|e| {
-            eprintln!("Error reading from stdin: {}", e);
-            std::process::exit(1);
-        });
-        buffer
-    };
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("Use this configuration file instead of looking for .tsqueryfmt.toml")
+                .value_parser(clap::value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            Arg::new("output-format")
+                .long("output-format")
+                .help("How to report the results of checks, lints and --write")
+                .value_parser(["text", "json", "github", "checkstyle", "sarif"])
+                .default_value("text")
+                .global(true),
+        )
+        .arg(
+            Arg::new("stdin-filepath")
+                .long("stdin-filepath")
+                .help("Treat stdin as the contents of this file when resolving settings, ignore files and diagnostics")
+                .value_parser(clap::value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            Arg::new("extensions")
+                .long("extensions")
+                .help("File extensions to look for in directories")
+                .value_delimiter(',')
+                .default_value("scm")
+                .global(true),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Format queries; the default when no subcommand is given")
+                .args(fmt_args()),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("List the queries that are not formatted; exits with 1 if there are any and 2 on errors")
+                .arg(input_arg())
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .help("Print a unified diff of the changes")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(print_config_arg())
+                .arg(watch_arg()),
+        )
+        .subcommand(
+            Command::new("tree")
+                .about("Print the parse tree of queries")
+                .arg(input_arg())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("How to print the tree; points are zero-based")
+                        .value_parser(["text", "json", "sexp", "dot"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::new("named-only")
+                        .long("named-only")
+                        .help("Leave out anonymous nodes such as punctuation")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Report syntax errors and captures that predicates use but patterns do not define")
+                .arg(input_arg())
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .help("Run again on the input files that change, until interrupted")
+                        .action(ArgAction::SetTrue)
+                        .requires("input"),
+                ),
+        )
+        .subcommand(
+            Command::new("captures")
+                .about("List the captures defined by queries")
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run a query against queries and print the captured nodes")
+                .arg(
+                    Arg::new("query")
+                        .help("A query file, or the text of a query")
+                        .required(true),
+                )
+                .arg(input_arg()),
+        );
 
-    if input.trim().is_empty() {
-        eprintln!("Error: Empty input");
-        std::process::exit(1);
-    }
+    let args = insert_default_subcommand(&command, std::env::args_os().collect());
+    let matches = command.get_matches_from(args);
 
-    if show_tree {
-        if let Some(tree_output) = print_tree(&input) {
-            println!("Parse tree:");
-            println!("{}", tree_output);
-            println!();
+    let code = match matches.subcommand().expect("a subcommand is required") {
+        ("check", matches) if matches.get_flag("watch") => {
+            watch::run(matches, |inputs| format::run_inputs(matches, inputs, true))
         }
+        ("check", matches) => format::run(matches, true),
+        ("tree", matches) => tree::run(matches),
+        ("lint", matches) if matches.get_flag("watch") => {
+            watch::run(matches, |inputs| lint::run_inputs(matches, inputs))
+        }
+        ("lint", matches) => lint::run(matches),
+        ("captures", matches) => captures::run(matches),
+        ("run", matches) => query::run(matches),
+        (_, matches) if matches.get_flag("watch") => {
+            watch::run(matches, |inputs| format::run_inputs(matches, inputs, false))
+        }
+        (_, matches) => format::run(matches, false),
+    };
+    if code != 0 {
+        std::process::exit(code);
     }
-
-    let formatted = format(&input, width).unwrap_or_else(|e| {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    });
-    println!("{}", formatted);
 }
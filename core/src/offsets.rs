@@ -0,0 +1,137 @@
+use std::ops::Range;
+
+use similar::{Algorithm, DiffTag, capture_diff_slices};
+
+use crate::{FormatError, FormatOptions, Formatter, tokens};
+
+/// The location of a significant token in the input and in the output of a
+/// formatting run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMapping {
+    /// The byte range of the token in the input.
+    pub input: Range<usize>,
+    /// The byte range of the token in the output.
+    pub output: Range<usize>,
+}
+
+/// Maps byte offsets in the input of a formatting run to byte offsets in its
+/// output, e.g. to keep the cursor, selections or diagnostics in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetMap {
+    tokens: Vec<TokenMapping>,
+    output_len: usize,
+}
+
+impl OffsetMap {
+    /// Returns the locations of all significant tokens, in source order.
+    pub fn tokens(&self) -> &[TokenMapping] {
+        &self.tokens
+    }
+
+    /// Maps an input byte offset to an output byte offset.
+    ///
+    /// An offset inside a token keeps its position within the token. An offset
+    /// in the whitespace between tokens keeps its distance to the preceding
+    /// token, but never moves past the following one.
+    pub fn map(&self, offset: usize) -> usize {
+        let index = self
+            .tokens
+            .partition_point(|token| token.input.start <= offset);
+
+        let Some(previous) = index.checked_sub(1).map(|i| &self.tokens[i]) else {
+            let limit = self
+                .tokens
+                .first()
+                .map_or(self.output_len, |token| token.output.start);
+            return offset.min(limit);
+        };
+
+        if offset <= previous.input.end {
+            return (previous.output.start + (offset - previous.input.start))
+                .min(previous.output.end);
+        }
+
+        let limit = self
+            .tokens
+            .get(index)
+            .map_or(self.output_len, |token| token.output.start);
+        (previous.output.end + (offset - previous.input.end)).min(limit)
+    }
+}
+
+/// Formats a Tree-sitter query string and returns the position of
+/// `cursor_offset` in the formatted output.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as
+/// [`format_with_options`](crate::format_with_options).
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{FormatOptions, format_with_cursor};
+///
+/// let input = "( identifier )  @variable";
+/// let (output, cursor) =
+///     format_with_cursor(input, input.find('@').unwrap(), &FormatOptions::default()).unwrap();
+/// assert_eq!(output, "(identifier) @variable");
+/// assert_eq!(cursor, output.find('@').unwrap());
+/// ```
+pub fn format_with_cursor(
+    input: &str,
+    cursor_offset: usize,
+    options: &FormatOptions,
+) -> Result<(String, usize), FormatError> {
+    let (output, offsets) = Formatter::new(options.clone())?.format_with_offset_map(input)?;
+    let cursor = offsets.map(cursor_offset);
+    Ok((output, cursor))
+}
+
+impl Formatter {
+    /// Formats a Tree-sitter query string and returns the formatted output
+    /// together with a map from input to output offsets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as
+    /// [`format_with_options`](crate::format_with_options).
+    pub fn format_with_offset_map(
+        &mut self,
+        input: &str,
+    ) -> Result<(String, OffsetMap), FormatError> {
+        let tree = self.parse(input)?;
+        let output = self.format_tree(&tree, input)?;
+        let output_tree = self.parse(&output)?;
+
+        let input_tokens = tokens(&tree, input);
+        let output_tokens = tokens(&output_tree, &output);
+
+        // Tokens are normally identical, but pair them up by diffing so that
+        // a divergence in best-effort mode only affects the tokens around it.
+        let input_texts: Vec<_> = input_tokens.iter().map(|token| &token.text).collect();
+        let output_texts: Vec<_> = output_tokens.iter().map(|token| &token.text).collect();
+        let mut mappings = Vec::with_capacity(input_tokens.len());
+        for op in capture_diff_slices(Algorithm::Myers, &input_texts, &output_texts) {
+            let (tag, input_range, output_range) = op.as_tag_tuple();
+            if tag != DiffTag::Equal {
+                continue;
+            }
+            for (a, b) in input_tokens[input_range]
+                .iter()
+                .zip(&output_tokens[output_range])
+            {
+                mappings.push(TokenMapping {
+                    input: a.range.start_byte..a.range.end_byte,
+                    output: b.range.start_byte..b.range.end_byte,
+                });
+            }
+        }
+
+        let offsets = OffsetMap {
+            tokens: mappings,
+            output_len: output.len(),
+        };
+        Ok((output, offsets))
+    }
+}
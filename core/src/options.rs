@@ -0,0 +1,149 @@
+/// How the children of a container are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Always put every child on its own line.
+    #[default]
+    Expanded,
+    /// Keep the children on one line if they fit within the maximum width,
+    /// otherwise put every child on its own line.
+    Auto,
+}
+
+/// What to do with nodes the formatter has no rule for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownNodePolicy {
+    /// Emit the source text of the node unchanged.
+    #[default]
+    Verbatim,
+    /// Fail with [`FormatError::UnknownNode`](crate::FormatError::UnknownNode).
+    Error,
+}
+
+/// Style settings used by [`format_with_options`](crate::format_with_options).
+///
+/// Use [`FormatOptions::builder`] to construct a value with non-default settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// Number of spaces used for each indentation level.
+    pub indent_width: usize,
+    /// The target line width.
+    pub max_width: usize,
+    /// Layout of the fields of a named node.
+    pub field_layout: Layout,
+    /// Layout of the alternatives of a `[...]` list.
+    pub list_layout: Layout,
+    /// Layout of the string arguments of a predicate.
+    pub predicate_layout: Layout,
+    /// The maximum number of consecutive blank lines kept between top-level
+    /// patterns and comments.
+    pub max_blank_lines: usize,
+    /// Whether the output ends with a newline.
+    pub trailing_newline: bool,
+    /// Whether to format input that contains syntax errors instead of
+    /// returning [`FormatError::Syntax`](crate::FormatError::Syntax).
+    pub best_effort: bool,
+    /// What to do with nodes the formatter has no rule for.
+    pub unknown_nodes: UnknownNodePolicy,
+    /// Whether to check that the output has the same significant tokens as
+    /// the input and that formatting it again does not change it.
+    pub verify: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            max_width: 80,
+            field_layout: Layout::Expanded,
+            list_layout: Layout::Expanded,
+            predicate_layout: Layout::Expanded,
+            max_blank_lines: 1,
+            trailing_newline: false,
+            best_effort: false,
+            unknown_nodes: UnknownNodePolicy::Verbatim,
+            verify: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Returns a builder starting from the default options.
+    pub fn builder() -> FormatOptionsBuilder {
+        FormatOptionsBuilder::default()
+    }
+}
+
+/// Builder for [`FormatOptions`].
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{FormatOptions, Layout};
+///
+/// let options = FormatOptions::builder()
+///     .indent_width(4)
+///     .max_width(100)
+///     .list_layout(Layout::Auto)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptionsBuilder {
+    options: FormatOptions,
+}
+
+impl FormatOptionsBuilder {
+    pub fn indent_width(mut self, indent_width: usize) -> Self {
+        self.options.indent_width = indent_width;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.options.max_width = max_width;
+        self
+    }
+
+    pub fn field_layout(mut self, layout: Layout) -> Self {
+        self.options.field_layout = layout;
+        self
+    }
+
+    pub fn list_layout(mut self, layout: Layout) -> Self {
+        self.options.list_layout = layout;
+        self
+    }
+
+    pub fn predicate_layout(mut self, layout: Layout) -> Self {
+        self.options.predicate_layout = layout;
+        self
+    }
+
+    pub fn max_blank_lines(mut self, max_blank_lines: usize) -> Self {
+        self.options.max_blank_lines = max_blank_lines;
+        self
+    }
+
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.options.trailing_newline = trailing_newline;
+        self
+    }
+
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.options.best_effort = best_effort;
+        self
+    }
+
+    pub fn unknown_nodes(mut self, policy: UnknownNodePolicy) -> Self {
+        self.options.unknown_nodes = policy;
+        self
+    }
+
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.options.verify = verify;
+        self
+    }
+
+    pub fn build(self) -> FormatOptions {
+        self.options
+    }
+}
@@ -0,0 +1,183 @@
+use std::ops::Range;
+
+use tree_sitter::{Node, Tree};
+
+use crate::{
+    Context, FormatError, FormatOptions, Formatter, collect_syntax_errors, map_program, render,
+    verbatim_region_end,
+};
+
+/// A region of a query, as sent by "format selection" requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Span {
+    /// A range of byte offsets.
+    Bytes(Range<usize>),
+    /// A range of zero-based line numbers. The end line is excluded.
+    Lines(Range<usize>),
+}
+
+impl Span {
+    /// Returns the byte offsets covered by this span in `input`. Offsets past
+    /// the end are clamped to it, and a reversed span is empty.
+    pub fn to_bytes(&self, input: &str) -> Range<usize> {
+        let range = match self {
+            Span::Bytes(range) => range.start.min(input.len())..range.end.min(input.len()),
+            Span::Lines(lines) => {
+                let line_start = |line: usize| {
+                    if line == 0 {
+                        return 0;
+                    }
+                    input
+                        .match_indices('\n')
+                        .nth(line - 1)
+                        .map_or(input.len(), |(i, _)| i + 1)
+                };
+                line_start(lines.start)..line_start(lines.end)
+            }
+        };
+        range.start..range.end.max(range.start)
+    }
+}
+
+/// The formatted text of a region of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedRange {
+    /// The byte range of the input that is replaced by `text`.
+    pub range: Range<usize>,
+    /// The formatted text of the region.
+    pub text: String,
+}
+
+impl FormattedRange {
+    /// Returns `input` with the region replaced by the formatted text.
+    pub fn apply(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        output.push_str(&input[..self.range.start]);
+        output.push_str(&self.text);
+        output.push_str(&input[self.range.end..]);
+        output
+    }
+}
+
+fn intersects(node: &Node, range: &Range<usize>) -> bool {
+    if range.is_empty() {
+        node.start_byte() <= range.start && range.start <= node.end_byte()
+    } else {
+        node.start_byte() < range.end && range.start < node.end_byte()
+    }
+}
+
+/// Formats the top-level patterns and comments that intersect `span`, leaving
+/// the rest of the input untouched.
+///
+/// Returns `None` if no top-level pattern or comment intersects `span`, which
+/// includes spans that start at or past the end of a non-empty input.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as
+/// [`format_with_options`](crate::format_with_options), except that only
+/// syntax errors inside the formatted region are reported.
+///
+/// # Example
+///
+/// ```
+/// use tree_sitter_query_formatter::{FormatOptions, Span, format_range};
+///
+/// let query = "(a  b: (c))\n(d  e: (f))";
+/// let formatted = format_range(query, &Span::Lines(1..2), &FormatOptions::default())
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(formatted.apply(query), "(a  b: (c))\n(d\n  e: (f))");
+/// ```
+pub fn format_range(
+    input: &str,
+    span: &Span,
+    options: &FormatOptions,
+) -> Result<Option<FormattedRange>, FormatError> {
+    Formatter::new(options.clone())?.format_range(input, span)
+}
+
+impl Formatter {
+    /// Formats the top-level patterns and comments that intersect `span`.
+    ///
+    /// See [`format_range`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`format_range`].
+    pub fn format_range(
+        &mut self,
+        input: &str,
+        span: &Span,
+    ) -> Result<Option<FormattedRange>, FormatError> {
+        let tree = self.parse(input)?;
+        format_tree_range(&tree, input, span.to_bytes(input), self.options())
+    }
+}
+
+fn format_tree_range(
+    tree: &Tree,
+    input: &str,
+    range: Range<usize>,
+    options: &FormatOptions,
+) -> Result<Option<FormattedRange>, FormatError> {
+    if !input.is_empty() && range.start >= input.len() {
+        return Ok(None);
+    }
+
+    let root_node = tree.root_node();
+    let mut cursor = root_node.walk();
+    let all_children: Vec<_> = root_node.children(&mut cursor).collect();
+
+    let (Some(mut start), Some(mut end)) = (
+        all_children
+            .iter()
+            .position(|child| intersects(child, &range)),
+        all_children
+            .iter()
+            .rposition(|child| intersects(child, &range)),
+    ) else {
+        return Ok(None);
+    };
+
+    // Regions kept unchanged by `; format-off` and `; format-ignore` must be
+    // formatted as a whole, starting with the comment that opens them.
+    let mut i = 0;
+    while i < all_children.len() {
+        match verbatim_region_end(&all_children, i, input) {
+            Some(region_end) => {
+                if i <= end && start <= region_end {
+                    start = start.min(i);
+                    end = end.max(region_end);
+                }
+                i = region_end + 1;
+            }
+            None => i += 1,
+        }
+    }
+
+    let children = &all_children[start..=end];
+    let (first, last) = (children[0], children[children.len() - 1]);
+
+    if !options.best_effort {
+        let mut ranges = Vec::new();
+        for &child in children {
+            collect_syntax_errors(child, &mut ranges);
+        }
+        if !ranges.is_empty() {
+            return Err(FormatError::Syntax { ranges });
+        }
+    }
+
+    let ctx = Context {
+        source: input,
+        options,
+    };
+    let text = render(map_program(children, &ctx)?, options)?;
+
+    Ok(Some(FormattedRange {
+        range: first.start_byte()..last.end_byte(),
+        text,
+    }))
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+
+use tree_sitter::{Node, Parser, Range, Tree};
+
+use crate::{FormatError, FormatOptions, collect_syntax_errors, format_tree};
+
+/// A significant token of a query, such as an identifier, a string, a capture,
+/// a quantifier, an anchor, a predicate name or a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// The kind of the syntax node the token was taken from.
+    pub kind: &'static str,
+    /// The source text of the token, without trailing whitespace for comments.
+    pub text: String,
+    /// The location of the token in its source.
+    pub range: Range,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` at {}:{}",
+            self.text,
+            self.range.start_point.row + 1,
+            self.range.start_point.column + 1
+        )
+    }
+}
+
+/// The first difference found when verifying formatted output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Divergence {
+    /// The formatted output contains syntax errors.
+    Syntax { ranges: Vec<Range> },
+    /// The significant tokens differ at `index`. A missing token means that
+    /// one of the sequences ended early.
+    Token {
+        index: usize,
+        input: Option<Token>,
+        output: Option<Token>,
+    },
+    /// Formatting the output again changes the zero-based `line`.
+    NotIdempotent {
+        line: usize,
+        first: String,
+        second: String,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Syntax { ranges } => {
+                write!(
+                    f,
+                    "formatted output contains {} syntax error(s)",
+                    ranges.len()
+                )?;
+                if let Some(range) = ranges.first() {
+                    write!(
+                        f,
+                        ", the first at {}:{}",
+                        range.start_point.row + 1,
+                        range.start_point.column + 1
+                    )?;
+                }
+                Ok(())
+            }
+            Divergence::Token {
+                index,
+                input,
+                output,
+            } => {
+                write!(f, "token {} differs: ", index)?;
+                match (input, output) {
+                    (Some(input), Some(output)) => {
+                        write!(f, "input has {}, output has {}", input, output)
+                    }
+                    (Some(input), None) => write!(f, "input has {}, output ended", input),
+                    (None, Some(output)) => write!(f, "input ended, output has {}", output),
+                    (None, None) => write!(f, "both ended"),
+                }
+            }
+            Divergence::NotIdempotent {
+                line,
+                first,
+                second,
+            } => write!(
+                f,
+                "formatting is not idempotent at line {}: `{}` became `{}`",
+                line + 1,
+                first,
+                second
+            ),
+        }
+    }
+}
+
+fn collect_tokens(node: Node, source: &str, tokens: &mut Vec<Token>) {
+    if node.child_count() == 0 || matches!(node.kind(), "string" | "capture") {
+        let mut text = &source[node.start_byte()..node.end_byte()];
+        if node.kind() == "comment" {
+            text = text.trim_end();
+        }
+        if !text.is_empty() {
+            tokens.push(Token {
+                kind: node.kind(),
+                text: text.to_string(),
+                range: node.range(),
+            });
+        }
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_tokens(child, source, tokens);
+    }
+}
+
+/// Returns the significant tokens of a parsed query in source order.
+pub fn tokens(tree: &Tree, source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    collect_tokens(tree.root_node(), source, &mut tokens);
+    tokens
+}
+
+/// Checks that `output` has the same significant tokens as the parsed `input`
+/// and that formatting it again does not change it.
+pub(crate) fn verify(
+    parser: &mut Parser,
+    tree: &Tree,
+    input: &str,
+    output: &str,
+    options: &FormatOptions,
+) -> Result<(), FormatError> {
+    let output_tree = parser.parse(output, None).ok_or(FormatError::Parse)?;
+
+    if !tree.root_node().has_error() && output_tree.root_node().has_error() {
+        let mut ranges = Vec::new();
+        collect_syntax_errors(output_tree.root_node(), &mut ranges);
+        return Err(FormatError::Verify(Box::new(Divergence::Syntax { ranges })));
+    }
+
+    let input_tokens = tokens(tree, input);
+    let output_tokens = tokens(&output_tree, output);
+    let len = input_tokens.len().max(output_tokens.len());
+    for index in 0..len {
+        let input_token = input_tokens.get(index);
+        let output_token = output_tokens.get(index);
+        let same = match (input_token, output_token) {
+            (Some(a), Some(b)) => a.kind == b.kind && a.text == b.text,
+            _ => false,
+        };
+        if !same {
+            return Err(FormatError::Verify(Box::new(Divergence::Token {
+                index,
+                input: input_token.cloned(),
+                output: output_token.cloned(),
+            })));
+        }
+    }
+
+    let second = format_tree(&output_tree, output, options)?;
+    if second != output {
+        let mut first_lines = output.lines();
+        let mut second_lines = second.lines();
+        let mut line = 0;
+        loop {
+            let (first, second) = (first_lines.next(), second_lines.next());
+            if first != second {
+                return Err(FormatError::Verify(Box::new(Divergence::NotIdempotent {
+                    line,
+                    first: first.unwrap_or_default().to_string(),
+                    second: second.unwrap_or_default().to_string(),
+                })));
+            }
+            if first.is_none() {
+                // Only the line endings differ.
+                return Err(FormatError::Verify(Box::new(Divergence::NotIdempotent {
+                    line,
+                    first: String::new(),
+                    second: String::new(),
+                })));
+            }
+            line += 1;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify_output(input: &str, output: &str) -> Divergence {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_tsquery::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(input, None).unwrap();
+        match verify(&mut parser, &tree, input, output, &FormatOptions::default()) {
+            Err(FormatError::Verify(divergence)) => *divergence,
+            result => panic!("expected a divergence, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn changed_token() {
+        let divergence = verify_output("(a) @b", "(a) @c");
+        let Divergence::Token {
+            index,
+            input: Some(input),
+            output: Some(output),
+        } = &divergence
+        else {
+            panic!("unexpected divergence {:?}", divergence);
+        };
+        assert_eq!(*index, 3);
+        assert_eq!((input.kind, input.text.as_str()), ("capture", "@b"));
+        assert_eq!((output.kind, output.text.as_str()), ("capture", "@c"));
+        assert_eq!(
+            divergence.to_string(),
+            "token 3 differs: input has `@b` at 1:5, output has `@c` at 1:5"
+        );
+    }
+
+    #[test]
+    fn missing_token() {
+        let divergence = verify_output("(a) @b", "(a)");
+        assert!(matches!(
+            divergence,
+            Divergence::Token {
+                index: 3,
+                input: Some(_),
+                output: None,
+            }
+        ));
+        assert_eq!(
+            divergence.to_string(),
+            "token 3 differs: input has `@b` at 1:5, output ended"
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let divergence = verify_output("(a) @b", "(a) @b)");
+        let Divergence::Syntax { ranges } = &divergence else {
+            panic!("unexpected divergence {:?}", divergence);
+        };
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(
+            divergence.to_string(),
+            "formatted output contains 1 syntax error(s), the first at 1:7"
+        );
+    }
+
+    #[test]
+    fn not_idempotent() {
+        let divergence = verify_output("(a)\n(c) @b", "(a)\n(c)  @b");
+        assert_eq!(
+            divergence,
+            Divergence::NotIdempotent {
+                line: 1,
+                first: "(c)  @b".to_string(),
+                second: "(c) @b".to_string(),
+            }
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "formatting is not idempotent at line 2: `(c)  @b` became `(c) @b`"
+        );
+    }
+}
@@ -0,0 +1,683 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tree-sitter-query-formatter"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run_stdin(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tree-sitter-query-formatter"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+const LIST: &str = "[\"a\" \"b\" \"c\" \"d\" \"e\" \"f\"] @k\n";
+
+#[test]
+fn config_discovery() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("queries/c")).unwrap();
+    fs::write(
+        dir.path().join(".tsqueryfmt.toml"),
+        "list_layout = \"auto\"\nmax_width = 20\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("queries/c/highlights.scm"), LIST).unwrap();
+
+    let output = run(dir.path(), &["queries/c/highlights.scm"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[\n  \"a\"\n  \"b\"\n  \"c\"\n  \"d\"\n  \"e\"\n  \"f\"\n] @k\n"
+    );
+
+    let output = run(dir.path(), &["--width", "80", "queries/c/highlights.scm"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), LIST);
+}
+
+#[test]
+fn config_overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("queries/c")).unwrap();
+    fs::write(
+        dir.path().join(".tsqueryfmt.toml"),
+        "list_layout = \"auto\"\nmax_width = 20\n\n\
+         [[overrides]]\nfiles = [\"injections.scm\"]\nmax_width = 120\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("queries/c/injections.scm"), LIST).unwrap();
+
+    let output = run(dir.path(), &["queries/c/injections.scm"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), LIST);
+}
+
+#[test]
+fn print_config() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("custom.toml"), "indent_width = 4\n").unwrap();
+    fs::write(dir.path().join("a.scm"), LIST).unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--config", "custom.toml", "--print-config", "a.scm"],
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("indent_width = 4\n"));
+    assert!(stdout.contains("max_width = 80\n"));
+}
+
+#[test]
+fn config_unknown_setting() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".tsqueryfmt.toml"), "indent = 4\n").unwrap();
+    fs::write(dir.path().join("a.scm"), LIST).unwrap();
+
+    let output = run(dir.path(), &["a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("unknown field `indent`")
+    );
+}
+
+#[test]
+fn editorconfig() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join(".editorconfig"),
+        "root = true\n\n[*.scm]\nindent_size = 4\nend_of_line = crlf\ninsert_final_newline = false\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("a.scm"), "(a b: (c))\n").unwrap();
+
+    let output = run(dir.path(), &["a.scm"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "(a\r\n    b: (c))"
+    );
+
+    // The configuration file takes precedence over .editorconfig.
+    fs::write(dir.path().join(".tsqueryfmt.toml"), "indent_width = 2\n").unwrap();
+    let output = run(dir.path(), &["a.scm"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(a\r\n  b: (c))");
+}
+
+#[test]
+fn editorconfig_tabs() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join(".editorconfig"),
+        "root = true\n\n[*.scm]\nindent_style = tab\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("a.scm"), "(a\n  b: (c))\n").unwrap();
+
+    let output = run(dir.path(), &["check", "a.scm"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Warning: a.scm: `indent_style = tab` from .editorconfig is not supported, indenting with spaces\n"
+    );
+
+    let output = run(dir.path(), &["check", "--output-format", "github", "a.scm"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("::warning file=a.scm,title=config::")
+    );
+}
+
+#[test]
+fn check() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("formatted.scm"), "(a\n  b: (c))\n").unwrap();
+    fs::write(dir.path().join("unformatted.scm"), "(a b: (c))\n").unwrap();
+    fs::write(dir.path().join("invalid.scm"), "(a b: (c)\n").unwrap();
+
+    let output = run(dir.path(), &["--check", "formatted.scm"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = run(dir.path(), &["--check", "unformatted.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "unformatted.scm\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("unformatted.scm")).unwrap(),
+        "(a b: (c))\n"
+    );
+
+    let output = run(dir.path(), &["--check", "invalid.scm"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn write() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.scm");
+    fs::write(&path, "(a b: (c))\r\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    }
+
+    let output = run(dir.path(), &["--write", "a.scm"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "1 file changed\n"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "(a\r\n  b: (c))\r\n");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    let output = run(dir.path(), &["--write", "a.scm"]);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "0 files changed\n"
+    );
+}
+
+#[test]
+fn write_format_off_crlf() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.scm");
+    fs::write(
+        &path,
+        "; format-off\r\n(a  (b))\r\n; format-on\r\n(c  (d))\r\n",
+    )
+    .unwrap();
+
+    let output = run(dir.path(), &["--write", "a.scm"]);
+    assert!(output.status.success());
+    let expected = "; format-off\r\n(a  (b))\r\n; format-on\r\n(c (d))\r\n";
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+
+    let output = run(dir.path(), &["--write", "a.scm"]);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "0 files changed\n"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+
+    let output = run(dir.path(), &["--check", "a.scm"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn directories_and_globs() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in [
+        "queries/c/highlights.scm",
+        "queries/c/injections.scm",
+        "queries/c/notes.txt",
+        "queries/c/generated.scm",
+        "queries/rust/highlights.scm",
+        "build/highlights.scm",
+    ] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "(a b: (c))\n").unwrap();
+    }
+    fs::write(dir.path().join(".gitignore"), "build/\n").unwrap();
+    fs::write(dir.path().join(".tsqueryfmtignore"), "generated.scm\n").unwrap();
+
+    let output = run(dir.path(), &["--check", "."]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "queries/c/highlights.scm\nqueries/c/injections.scm\nqueries/rust/highlights.scm\n"
+    );
+
+    let output = run(dir.path(), &["--check", "queries/*/highlights.scm"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "queries/c/highlights.scm\nqueries/rust/highlights.scm\n"
+    );
+
+    let output = run(dir.path(), &["--check", "--extensions", "txt", "queries"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "queries/c/notes.txt\n"
+    );
+
+    let output = run(
+        dir.path(),
+        &[
+            "--check",
+            "queries/c/generated.scm",
+            "build/highlights.scm",
+            "queries/c/injections.scm",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "queries/c/injections.scm\n"
+    );
+
+    let output = run(dir.path(), &["--write", "queries"]);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "3 files changed\n"
+    );
+
+    let output = run(dir.path(), &["--check", "missing"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn diff() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a)\n(b c: (d))\n").unwrap();
+
+    let output = run(dir.path(), &["--diff", "a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "--- a/a.scm\n+++ b/a.scm\n@@ -1,2 +1,3 @@\n (a)\n-(b c: (d))\n+(b\n+  c: (d))\n"
+    );
+
+    let output = run(dir.path(), &["--diff", "--color", "always", "a.scm"]);
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("\x1b[31m-(b c: (d))\x1b[0m\n")
+    );
+
+    fs::write(dir.path().join("a.scm"), "(a)\n").unwrap();
+    let output = run(dir.path(), &["--diff", "a.scm"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn edits() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a  (b))\n").unwrap();
+    fs::write(dir.path().join("b.scm"), "(a  (b))\r\n(c  (d))\r\n").unwrap();
+
+    let output = run(dir.path(), &["--edits", "a.scm"]);
+    assert_eq!(output.status.code(), Some(0));
+    let edits: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        edits,
+        serde_json::json!([{"start": 2, "end": 4, "new_text": " "}])
+    );
+
+    // Offsets count the carriage returns, which the edits leave in place.
+    let output = run(dir.path(), &["--edits", "b.scm"]);
+    let edits: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        edits,
+        serde_json::json!([
+            {"start": 2, "end": 4, "new_text": " "},
+            {"start": 12, "end": 14, "new_text": " "},
+        ])
+    );
+
+    let output = run(dir.path(), &["--edits", "a.scm", "b.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: --print-config and --edits expect a single input\n"
+    );
+}
+
+#[test]
+fn output_format_json() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a b: (c))\n").unwrap();
+    fs::write(dir.path().join("b.scm"), "(a b: (c)\n").unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--check", "--output-format", "json", "a.scm", "b.scm"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stderr.is_empty());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(reports[0]["path"], "a.scm");
+    assert_eq!(reports[0]["status"], "changed");
+    assert_eq!(reports[0]["diagnostics"][0]["rule"], "format");
+    assert_eq!(reports[1]["status"], "failed");
+    assert_eq!(reports[1]["diagnostics"][0]["rule"], "syntax");
+    assert_eq!(reports[1]["diagnostics"][0]["start"]["line"], 1);
+}
+
+#[test]
+fn verify() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a  (b))\n").unwrap();
+    // Best-effort formatting closes the pattern, which verification reports.
+    fs::write(dir.path().join("b.scm"), "(a . (b) .\n").unwrap();
+
+    let output = run(dir.path(), &["--verify", "a.scm"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(a (b))\n");
+
+    let output = run(dir.path(), &["--verify", "--best-effort", "b.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: b.scm: token 7 differs: input ended, output has `)` at 1:11\n"
+    );
+
+    let output = run(
+        dir.path(),
+        &[
+            "--check",
+            "--verify",
+            "--best-effort",
+            "--output-format",
+            "json",
+            "a.scm",
+            "b.scm",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(reports[0]["status"], "changed");
+    assert_eq!(reports[0]["diagnostics"][0]["rule"], "format");
+    assert_eq!(reports[1]["status"], "failed");
+    assert_eq!(reports[1]["diagnostics"][0]["rule"], "verify");
+    assert_eq!(
+        reports[1]["diagnostics"][0]["message"],
+        "token 7 differs: input ended, output has `)` at 1:11"
+    );
+}
+
+#[test]
+fn output_format_github() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a)\n(b c: (d))\n").unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--check", "--output-format", "github", "a.scm"],
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "::error file=a.scm,line=2,col=3,endLine=2,endColumn=4,title=format::File is not formatted\n"
+    );
+}
+
+#[test]
+fn stdin_filepath() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("queries/c")).unwrap();
+    fs::write(
+        dir.path().join(".tsqueryfmt.toml"),
+        "list_layout = \"auto\"\nmax_width = 20\n\n\
+         [[overrides]]\nfiles = [\"injections.scm\"]\nmax_width = 120\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join(".tsqueryfmtignore"), "generated.scm\n").unwrap();
+
+    let output = run_stdin(
+        dir.path(),
+        &["--stdin-filepath", "queries/c/injections.scm"],
+        LIST,
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), LIST);
+
+    let output = run_stdin(
+        dir.path(),
+        &["--stdin-filepath", "queries/c/highlights.scm"],
+        LIST,
+    );
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("[\n"));
+
+    let input = "(a  b: (c))";
+    let output = run_stdin(
+        dir.path(),
+        &["--stdin-filepath", "queries/c/generated.scm"],
+        input,
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), input);
+
+    let output = run_stdin(
+        dir.path(),
+        &["--stdin-filepath", "queries/c/highlights.scm"],
+        "(a b: (c)",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: syntax error at queries/c/highlights.scm:1:1\n"
+    );
+}
+
+#[test]
+fn subcommands() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a)  @b\n").unwrap();
+
+    let output = run(dir.path(), &["a.scm"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(a) @b\n");
+    let output = run(dir.path(), &["fmt", "a.scm"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(a) @b\n");
+
+    let output = run(dir.path(), &["check", "a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.scm\n");
+
+    // Global options may come before the subcommand.
+    let output = run(dir.path(), &["--width", "100", "check", "a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.scm\n");
+    let output = run(dir.path(), &["--color", "never", "tree", "a.scm"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("program [0:0-1:0]\n")
+    );
+    let output = run(dir.path(), &["--width", "100", "--check", "a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run_stdin(dir.path(), &["captures"], "(a) @b (c (d) @a) @b");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "@a\n@b\n");
+
+    let output = run_stdin(dir.path(), &["run", "(capture) @c"], "(a) @b");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "<stdin>:1:5: @c @b\n"
+    );
+
+    let output = run(dir.path(), &["run", "(capture"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn lint() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run_stdin(
+        dir.path(),
+        &["lint"],
+        "(a) @x (#eq? @x \"a\")\n((b) @y (#eq? @z \"b\"))\n",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: capture `@z` is not defined in this pattern at 2:15\n"
+    );
+
+    let output = run_stdin(dir.path(), &["lint"], "(a) @x (#eq? @x \"a\")\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn tree_formats() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = run_stdin(dir.path(), &["tree", "--format", "sexp"], "(a");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "(program 0..2 [0, 0] - [0, 2]\n  (named_node 0..2 [0, 0] - [0, 2]\n    (\"(\" 0..1 [0, 0] - [0, 1])\n    name: (identifier 1..2 [0, 1] - [0, 2])\n    (MISSING \")\" 2..2 [0, 2] - [0, 2])))\n"
+    );
+
+    let output = run_stdin(dir.path(), &["tree", "--format", "json"], "(a) @b");
+    let tree: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let capture = &tree["children"][0]["children"][3];
+    assert_eq!(capture["kind"], "capture");
+    assert_eq!(capture["named"], true);
+    assert_eq!(capture["start_byte"], 4);
+    assert_eq!(capture["end_point"]["column"], 6);
+    assert_eq!(capture["children"][1]["field"], "name");
+    assert_eq!(capture["children"][1]["missing"], false);
+
+    let output = run_stdin(dir.path(), &["tree", "--format", "dot"], "(a)");
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph tree {\n"));
+    assert!(dot.contains("  n1 -> n3 [label=\"name\"];\n"));
+}
+
+#[test]
+fn tree_text() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = run_stdin(dir.path(), &["tree", "--color", "never"], "(a @@)\n(b");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "program [0:0-1:2]
+  named_node [0:0-0:6]
+    \"(\" [0:0-0:1]
+    name: identifier [0:1-0:2] \"a\"
+    ERROR [0:3-0:5] <- syntax error
+      | (a @@)
+      |    ^^
+      \"@\" [0:3-0:4]
+      \"@\" [0:4-0:5]
+    \")\" [0:5-0:6]
+  named_node [1:0-1:2]
+    \"(\" [1:0-1:1]
+    name: identifier [1:1-1:2] \"b\"
+    MISSING \")\" [1:2-1:2] <- missing
+      | (b
+      |   ^
+"
+    );
+
+    let output = run_stdin(dir.path(), &["tree", "--named-only"], "(a) @b");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "program [0:0-0:6]
+  named_node [0:0-0:6]
+    name: identifier [0:1-0:2] \"a\"
+    capture [0:4-0:6]
+      name: identifier [0:5-0:6] \"b\"
+"
+    );
+}
+
+#[test]
+fn watch() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("queries")).unwrap();
+    fs::write(dir.path().join("queries/a.scm"), "(a) @b\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tree-sitter-query-formatter"))
+        .current_dir(dir.path())
+        .args(["lint", "--watch", "queries"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let next_line = || receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+
+    assert_eq!(next_line(), "Processed 1 file, watching for changes");
+    fs::write(dir.path().join("queries/b.scm"), "(a\n").unwrap();
+    assert_eq!(next_line(), "Error: missing `)` at queries/b.scm:1:3");
+    assert_eq!(next_line(), "Processed 1 file, watching for changes");
+    fs::write(dir.path().join("queries/b.scm"), "(a) @b\n").unwrap();
+    assert_eq!(next_line(), "Processed 1 file, watching for changes");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn empty_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("queries")).unwrap();
+    fs::write(dir.path().join("queries/empty.scm"), "").unwrap();
+    fs::write(dir.path().join("queries/blank.scm"), "\n  \n").unwrap();
+    fs::write(dir.path().join("queries/a.scm"), "(a) @b\n").unwrap();
+
+    let output = run(dir.path(), &["check", "queries"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = run_stdin(dir.path(), &[], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Empty input\n"
+    );
+}
+
+#[test]
+fn output_format_requires_report() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.scm"), "(a  b: (c))\n").unwrap();
+
+    let output = run(dir.path(), &["--output-format", "json", "a.scm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: --output-format json cannot be used when printing formatted queries\n"
+    );
+
+    let output = run(
+        dir.path(),
+        &["check", "--diff", "--output-format", "json", "a.scm"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: --output-format json cannot be used when printing diffs\n"
+    );
+}
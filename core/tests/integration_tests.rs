@@ -1,4 +1,7 @@
-use tree_sitter_query_formatter::format;
+use tree_sitter_query_formatter::{
+    FormatError, FormatOptions, Formatter, Layout, Span, UnknownNodePolicy, apply_edits, format,
+    format_edits, format_range, format_with_cursor, format_with_options,
+};
 
 macro_rules! format_test {
     ($name:ident) => {
@@ -8,6 +11,9 @@ macro_rules! format_test {
             let expected = include_str!(concat!("fixtures/expected/", stringify!($name), ".txt"));
             let result = format(input.trim(), 80).unwrap();
             assert_eq!(result.trim(), expected.trim());
+
+            let options = FormatOptions::builder().verify(true).build();
+            format_with_options(input.trim(), &options).unwrap();
         }
     };
 }
@@ -17,11 +23,18 @@ format_test!(anchor);
 format_test!(anchor_end);
 format_test!(anchor_end_node);
 format_test!(anonymous);
+format_test!(blank_lines);
 format_test!(capture);
 format_test!(comment);
+format_test!(comment_header);
+format_test!(comment_nested);
+format_test!(comment_positions);
+format_test!(comment_trailing);
 format_test!(directive);
 format_test!(error);
 format_test!(fields);
+format_test!(fields_capture);
+format_test!(format_off);
 format_test!(grouping);
 format_test!(grouping_long);
 format_test!(grouping_quantification);
@@ -37,3 +50,239 @@ format_test!(quantification_question_mark);
 format_test!(simple);
 format_test!(supertype);
 format_test!(wildcard);
+
+#[test]
+fn indent_width() {
+    let options = FormatOptions::builder().indent_width(4).build();
+    let input = include_str!("fixtures/input/fields.txt");
+    let result = format_with_options(input.trim(), &options).unwrap();
+    assert_eq!(
+        result,
+        "(assignment_expression\n    left: (member_expression\n        object: (call_expression)))"
+    );
+}
+
+#[test]
+fn layout_auto() {
+    let options = FormatOptions::builder()
+        .field_layout(Layout::Auto)
+        .list_layout(Layout::Auto)
+        .predicate_layout(Layout::Auto)
+        .build();
+    let input =
+        "(binary_expression operator: [\"==\" \"!=\"] right: (null) (#any-of? @op \"a\" \"b\"))";
+    let result = format_with_options(input, &options).unwrap();
+    assert_eq!(result, input);
+}
+
+#[test]
+fn layout_auto_breaks_when_too_long() {
+    let options = FormatOptions::builder()
+        .max_width(20)
+        .list_layout(Layout::Auto)
+        .build();
+    let result = format_with_options("[\"break\" \"delete\" \"else\"] @keyword", &options).unwrap();
+    assert_eq!(
+        result,
+        "[\n  \"break\"\n  \"delete\"\n  \"else\"\n] @keyword"
+    );
+}
+
+#[test]
+fn trailing_newline() {
+    let options = FormatOptions::builder().trailing_newline(true).build();
+    let result = format_with_options("(call_expression)", &options).unwrap();
+    assert_eq!(result, "(call_expression)\n");
+}
+
+#[test]
+fn syntax_error() {
+    let input = "(call_expression)\n(identifier @name";
+    match format(input, 80) {
+        Err(FormatError::Syntax { ranges }) => {
+            assert_eq!(ranges.len(), 1);
+            assert_eq!(ranges[0].start_point.row, 1);
+        }
+        result => panic!("expected a syntax error, got {:?}", result),
+    }
+}
+
+#[test]
+fn syntax_error_best_effort() {
+    let options = FormatOptions::builder().best_effort(true).build();
+    let result = format_with_options("(call_expression) )", &options);
+    assert!(result.is_ok());
+}
+
+#[test]
+fn unknown_node_verbatim() {
+    let options = FormatOptions::builder().best_effort(true).build();
+    let result = format_with_options("(call_expression) )", &options).unwrap();
+    assert_eq!(result, "(call_expression)\n)");
+}
+
+#[test]
+fn unknown_node_error() {
+    let options = FormatOptions::builder()
+        .best_effort(true)
+        .unknown_nodes(UnknownNodePolicy::Error)
+        .build();
+    match format_with_options("(call_expression) )", &options) {
+        Err(FormatError::UnknownNode { kind, range }) => {
+            assert_eq!(kind, "ERROR");
+            assert_eq!(range.start_byte, 18);
+        }
+        result => panic!("expected an unknown node error, got {:?}", result),
+    }
+}
+
+#[test]
+fn formatter_reuse() {
+    let mut formatter = Formatter::new(FormatOptions::default()).unwrap();
+    let capture = include_str!("fixtures/input/capture.txt").trim();
+    let fields = include_str!("fixtures/input/fields.txt").trim();
+    assert_eq!(
+        formatter.format(capture).unwrap(),
+        include_str!("fixtures/expected/capture.txt").trim()
+    );
+    assert_eq!(
+        formatter.format(fields).unwrap(),
+        include_str!("fixtures/expected/fields.txt").trim()
+    );
+}
+
+#[test]
+fn formatter_parsed_tree() {
+    let input = include_str!("fixtures/input/capture.txt").trim();
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_tsquery::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(input, None).unwrap();
+
+    let mut formatter = Formatter::new(FormatOptions::default()).unwrap();
+    assert_eq!(
+        formatter.format_tree(&tree, input).unwrap(),
+        include_str!("fixtures/expected/capture.txt").trim()
+    );
+}
+
+#[test]
+fn range_bytes() {
+    let input = "(a  b: (c))\n\n(d  e: (f))\n(g  h: (i))";
+    let start = input.find("(d").unwrap();
+    let formatted = format_range(
+        input,
+        &Span::Bytes(start..start + 1),
+        &FormatOptions::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(formatted.range, start..start + 11);
+    assert_eq!(
+        formatted.apply(input),
+        "(a  b: (c))\n\n(d\n  e: (f))\n(g  h: (i))"
+    );
+}
+
+#[test]
+fn range_lines() {
+    let input = "(a  b: (c))\n(d  e: (f))\n(g  h: (i))";
+    let formatted = format_range(input, &Span::Lines(1..3), &FormatOptions::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        formatted.apply(input),
+        "(a  b: (c))\n(d\n  e: (f))\n(g\n  h: (i))"
+    );
+}
+
+#[test]
+fn range_outside_patterns() {
+    let input = "(a)\n\n\n(b)";
+    let result = format_range(input, &Span::Lines(1..2), &FormatOptions::default()).unwrap();
+    assert_eq!(result, None);
+}
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn range_past_end() {
+    let input = "(a  b: (c))\n(d  e: (f))";
+    let options = FormatOptions::default();
+    for span in [Span::Lines(5..9), Span::Lines(2..1), Span::Bytes(30..40)] {
+        assert_eq!(format_range(input, &span, &options).unwrap(), None);
+    }
+}
+
+#[test]
+fn edits() {
+    let input = include_str!("fixtures/input/capture.txt").trim();
+    let expected = include_str!("fixtures/expected/capture.txt").trim();
+    let edits = format_edits(input, &FormatOptions::default()).unwrap();
+    assert_eq!(edits.len(), 2);
+    assert!(edits.iter().all(|edit| input[edit.range.clone()] == *" "));
+    assert_eq!(apply_edits(input, &edits), expected);
+}
+
+#[test]
+fn edits_formatted_input() {
+    let input = include_str!("fixtures/expected/nested.txt").trim();
+    let edits = format_edits(input, &FormatOptions::default()).unwrap();
+    assert_eq!(edits, vec![]);
+}
+
+#[test]
+fn cursor() {
+    let input = include_str!("fixtures/input/capture.txt").trim();
+    let expected = include_str!("fixtures/expected/capture.txt").trim();
+    let options = FormatOptions::default();
+
+    let cursor = input.find("@args").unwrap() + 2;
+    let (output, new_cursor) = format_with_cursor(input, cursor, &options).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(new_cursor, expected.find("@args").unwrap() + 2);
+
+    let cursor = input.find(" arguments:").unwrap();
+    let (_, new_cursor) = format_with_cursor(input, cursor, &options).unwrap();
+    assert_eq!(&expected[new_cursor..], "\n  arguments: (arguments) @args)");
+}
+
+#[test]
+fn offset_map() {
+    let input = "(a   b: (c))  ";
+    let mut formatter = Formatter::new(FormatOptions::default()).unwrap();
+    let (output, offsets) = formatter.format_with_offset_map(input).unwrap();
+    assert_eq!(output, "(a\n  b: (c))");
+    assert_eq!(offsets.tokens().len(), 8);
+    for token in offsets.tokens() {
+        assert_eq!(input[token.input.clone()], output[token.output.clone()]);
+    }
+    assert_eq!(offsets.map(0), 0);
+    assert_eq!(offsets.map(input.len()), output.len());
+}
+
+#[test]
+fn max_blank_lines() {
+    let input = include_str!("fixtures/input/blank_lines.txt").trim();
+    let options = FormatOptions::builder().max_blank_lines(0).build();
+    let result = format_with_options(input, &options).unwrap();
+    assert!(!result.contains("\n\n"));
+}
+
+#[test]
+fn format_off_crlf() {
+    let input = "; format-off\r\n(a  (b))\r\n; format-on\r\n(c  (d))\r\n";
+    let options = FormatOptions::builder().verify(true).build();
+    let formatted = format_with_options(input, &options).unwrap();
+    assert_eq!(formatted, "; format-off\n(a  (b))\n; format-on\n(c (d))");
+}
+
+#[test]
+fn range_format_off() {
+    let input = "; format-off\n(a  b: (c))\n(d  e: (f))";
+    let formatted = format_range(input, &Span::Lines(2..3), &FormatOptions::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(formatted.range, 0..input.len());
+    assert_eq!(formatted.apply(input), input);
+}
@@ -1,4 +1,4 @@
-use tree_sitter_query_formatter::format;
+use tree_sitter_query_formatter::{FormatError, format};
 
 wit_bindgen::generate!({
     world: "host",
@@ -6,9 +6,27 @@ wit_bindgen::generate!({
 
 struct MyHost;
 
+/// Describes a formatting error, with one line for each syntax error.
+fn error_message(error: FormatError) -> String {
+    match error {
+        FormatError::Syntax { ranges } => ranges
+            .iter()
+            .map(|range| {
+                format!(
+                    "syntax error at {}:{}",
+                    range.start_point.row + 1,
+                    range.start_point.column + 1
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        e => e.to_string(),
+    }
+}
+
 impl Guest for MyHost {
     fn format(query: String) -> Result<String, String> {
-        format(&query, 80).map_err(|e| e.to_string())
+        format(&query, 80).map_err(error_message)
     }
 }
 